@@ -0,0 +1,87 @@
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+use valence::bench::InitPacketsBench;
+use valence::prelude::*;
+
+/// The number of entities in the partition cell being loaded.
+const ENTITY_COUNT: usize = 500;
+
+/// The numbers of clients loading the cell in the same tick.
+const VIEWER_COUNTS: [usize; 3] = [1, 10, 100];
+
+/// The number of ticks to average over.
+const ITERATIONS: u32 = 200;
+
+struct Game;
+
+impl Config for Game {
+    type ServerState = ();
+    type ClientState = ();
+    type EntityState = ();
+    type WorldState = ();
+    type ChunkState = ();
+    type PlayerListState = ();
+    type InventoryState = ();
+}
+
+pub fn main() {
+    let threshold = Game.compression_threshold();
+    let mut bench = InitPacketsBench::<Game>::new(EntityKind::Zombie, ENTITY_COUNT, threshold);
+
+    // Both paths must send the same packets.
+    let mut uncached = vec![];
+    bench.write_uncached(&mut uncached);
+    bench.build_cache();
+    let mut cached = vec![];
+    bench.write_cached(&mut cached);
+    assert_eq!(uncached, cached, "cached init packets differ");
+
+    println!(
+        "Loading {ENTITY_COUNT} entities ({} bytes per viewer), {ITERATIONS} iterations",
+        uncached.len()
+    );
+
+    let mut out = vec![];
+
+    for viewers in VIEWER_COUNTS {
+        let uncached = time(|| {
+            for _ in 0..viewers {
+                out.clear();
+                bench.write_uncached(&mut out);
+                black_box(&out);
+            }
+        });
+
+        let cached = time(|| {
+            bench.build_cache();
+
+            for _ in 0..viewers {
+                out.clear();
+                bench.write_cached(&mut out);
+                black_box(&out);
+            }
+        });
+
+        println!(
+            "{viewers:>3} viewers: uncached {:>10.3?}, cached {:>10.3?} ({:.1}x)",
+            uncached,
+            cached,
+            uncached.as_secs_f64() / cached.as_secs_f64()
+        );
+    }
+}
+
+/// Returns the average duration of `f` over [`ITERATIONS`] runs.
+fn time(mut f: impl FnMut()) -> Duration {
+    // Warm up the buffers.
+    f();
+
+    let start = Instant::now();
+
+    for _ in 0..ITERATIONS {
+        f();
+    }
+
+    start.elapsed() / ITERATIONS
+}
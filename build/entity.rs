@@ -235,6 +235,9 @@ impl Value {
 
 type Entities = BTreeMap<String, Entity>;
 
+/// The fields and flags of the base entity with accessors on `TrackedData`.
+const BASE_ENTITY_ACCESSORS: &[&str] = &["silent", "no_gravity"];
+
 pub fn build() -> anyhow::Result<TokenStream> {
     let entities = serde_json::from_str::<Entities>(include_str!("../extracted/entities.json"))?
         .into_iter()
@@ -380,6 +383,18 @@ pub fn build() -> anyhow::Result<TokenStream> {
             }
         });
 
+        let copy_fields_stmts = fields.iter().map(|&field| {
+            let field_name = ident(&field.name);
+            let field_index = field.index;
+
+            quote! {
+                if (fields >> #field_index) & 1 == 1 && self.#field_name != src.#field_name {
+                    self.#field_name = src.#field_name.clone();
+                    self.__modified_flags |= 1 << #field_index as #modified_flags_type;
+                }
+            }
+        });
+
         quote! {
             pub struct #struct_name {
                 /// Contains a set bit for every modified field.
@@ -409,11 +424,89 @@ pub fn build() -> anyhow::Result<TokenStream> {
                     self.__modified_flags = 0;
                 }
 
+                /// Copies the fields with a set bit in `fields` from `src`,
+                /// marking the fields that changed as modified.
+                pub(crate) fn copy_fields(&mut self, src: &Self, fields: u64) {
+                    #(#copy_fields_stmts)*
+                }
+
                 #(#getter_setters)*
             }
         }
     });
 
+    // Every entity inherits the fields of the base entity, so accessors for them
+    // can be provided on `TrackedData` directly. Only the fields wrapped by
+    // `Entity` are generated.
+    let base_entity_getter_setters = entities["Entity"].fields.iter().flat_map(|field| {
+        let accessors = if field.bits.is_empty() {
+            vec![(
+                field.name.as_str(),
+                field.default_value.field_type(),
+                field.default_value.getter_return_type(),
+            )]
+        } else {
+            field
+                .bits
+                .iter()
+                .map(|bit| (bit.name.as_str(), quote!(bool), quote!(bool)))
+                .collect()
+        };
+
+        accessors
+            .into_iter()
+            .filter(|(name, ..)| BASE_ENTITY_ACCESSORS.contains(name))
+            .map(|(name, typ, return_type)| {
+                let arg_name = ident(name);
+                let getter_name = ident(format!("get_{name}"));
+                let setter_name = ident(format!("set_{name}"));
+                let names = &concrete_entity_names;
+
+                quote! {
+                    pub(crate) fn #getter_name(&self) -> #return_type {
+                        match self {
+                            #(Self::#names(e) => e.#getter_name(),)*
+                        }
+                    }
+
+                    pub(crate) fn #setter_name(&mut self, #arg_name: impl Into<#typ>) {
+                        let #arg_name = #arg_name.into();
+
+                        match self {
+                            #(Self::#names(e) => e.#setter_name(#arg_name),)*
+                        }
+                    }
+                }
+            })
+    });
+
+    // Clients can broadcast the fields of their own player data by index.
+    let player_fields = collect_all_fields("Player", &entities);
+
+    let player_data_field_variants = player_fields.iter().map(|field| {
+        let name = ident(field.name.to_pascal_case());
+        let index = field.index as isize;
+
+        let doc = if field.bits.is_empty() {
+            quote!()
+        } else {
+            let bits = field
+                .bits
+                .iter()
+                .map(|bit| format!("`{}`", bit.name))
+                .collect::<Vec<_>>()
+                .join(", ");
+            let doc = format!(" Contains the bits {bits}.");
+
+            quote!(#[doc = #doc])
+        };
+
+        quote! {
+            #doc
+            #name = #index,
+        }
+    });
+
     let translation_key_arms = concrete_entities.iter().map(|(k, v)| {
         let name = ident(k);
         let key = v
@@ -445,6 +538,17 @@ pub fn build() -> anyhow::Result<TokenStream> {
             #(#concrete_entity_names(#concrete_entity_names),)*
         }
 
+        /// A field of a client's own [`Player`] data. Used with
+        /// [`Client::set_player_field_broadcast`].
+        ///
+        /// The discriminant of each variant is the field's metadata index.
+        ///
+        /// [`Client::set_player_field_broadcast`]: crate::client::Client::set_player_field_broadcast
+        #[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+        pub enum PlayerDataField {
+            #(#player_data_field_variants)*
+        }
+
         impl TrackedData {
             pub(super) fn new(kind: EntityKind) -> Self {
                 match kind {
@@ -487,6 +591,8 @@ pub fn build() -> anyhow::Result<TokenStream> {
                     #(Self::#concrete_entity_names(e) => e.clear_modifications(),)*
                 }
             }
+
+            #(#base_entity_getter_setters)*
         }
 
         #(#concrete_entity_structs)*
@@ -7,6 +7,7 @@ use proc_macro2::{Ident, Span};
 
 mod entity;
 mod entity_event;
+mod particle;
 
 pub fn main() -> anyhow::Result<()> {
     println!("cargo:rerun-if-changed=extracted/");
@@ -14,6 +15,7 @@ pub fn main() -> anyhow::Result<()> {
     let generators = [
         (entity::build as fn() -> _, "entity.rs"),
         (entity_event::build, "entity_event.rs"),
+        (particle::build, "particle.rs"),
     ];
 
     let out_dir = env::var_os("OUT_DIR").context("can't get OUT_DIR env var")?;
@@ -0,0 +1,43 @@
+use heck::ToPascalCase;
+use proc_macro2::TokenStream;
+use quote::quote;
+use serde::Deserialize;
+
+use crate::ident;
+
+#[derive(Deserialize, Clone, Debug)]
+struct Particle {
+    id: i32,
+    name: String,
+}
+
+/// Particles in the registry without a variant in `Particle`.
+const UNSUPPORTED_PARTICLES: &[&str] = &["vibration"];
+
+pub fn build() -> anyhow::Result<TokenStream> {
+    let particles: Vec<Particle> =
+        serde_json::from_str(include_str!("../extracted/particles.json"))?;
+
+    let id_arms = particles
+        .iter()
+        .filter(|p| !UNSUPPORTED_PARTICLES.contains(&p.name.as_str()))
+        .map(|p| {
+            let name = ident(p.name.to_pascal_case());
+            let id = p.id;
+
+            quote! {
+                Self::#name { .. } => #id,
+            }
+        });
+
+    Ok(quote! {
+        impl Particle {
+            /// Returns the ID of this particle type in the particle registry.
+            pub fn id(&self) -> i32 {
+                match self {
+                    #(#id_arms)*
+                }
+            }
+        }
+    })
+}
@@ -69,6 +69,10 @@ impl Config for Game {
         }]
     }
 
+    fn auto_manage_player_list(&self) -> bool {
+        true
+    }
+
     async fn server_list_ping(
         &self,
         _server: &SharedServer<Self>,
@@ -141,17 +145,6 @@ impl Config for Game {
                 client.teleport(spawn_pos, 0.0, 0.0);
                 client.set_player_list(server.state.player_list.clone());
 
-                if let Some(id) = &server.state.player_list {
-                    server.player_lists[id].insert(
-                        client.uuid(),
-                        client.username(),
-                        client.textures().cloned(),
-                        client.game_mode(),
-                        0,
-                        None,
-                    );
-                }
-
                 client.send_message("Welcome to Conway's game of life in Minecraft!".italic());
                 client.send_message(
                     "Sneak and hold the left mouse button to bring blocks to life.".italic(),
@@ -171,10 +164,11 @@ impl Config for Game {
                             let index = position.x as usize + position.z as usize * SIZE_X;
 
                             if !server.state.board[index] {
-                                client.play_sound(
+                                // Everyone nearby hears cells being brought to life.
+                                world.play_sound(
+                                    Vec3::new(position.x, position.y, position.z).as_::<f64>(),
                                     Ident::new("minecraft:block.note_block.banjo").unwrap(),
                                     SoundCategory::Block,
-                                    Vec3::new(position.x, position.y, position.z).as_(),
                                     0.5f32,
                                     1f32,
                                 );
@@ -195,9 +189,6 @@ impl Config for Game {
             if client.is_disconnected() {
                 self.player_count.fetch_sub(1, Ordering::SeqCst);
                 player.set_deleted(true);
-                if let Some(id) = &server.state.player_list {
-                    server.player_lists[id].remove(client.uuid());
-                }
                 return false;
             }
 
@@ -0,0 +1,33 @@
+package rs.valence.extractor.extractors;
+
+import com.google.gson.JsonArray;
+import com.google.gson.JsonElement;
+import com.google.gson.JsonObject;
+import net.minecraft.util.registry.Registry;
+import rs.valence.extractor.Main;
+
+public class Particles implements Main.Extractor {
+    public Particles() {
+    }
+
+    @Override
+    public String fileName() {
+        return "particles.json";
+    }
+
+    @Override
+    public JsonElement extract() {
+        var particlesJson = new JsonArray();
+
+        for (var particle : Registry.PARTICLE_TYPE) {
+            var particleJson = new JsonObject();
+
+            particleJson.addProperty("id", Registry.PARTICLE_TYPE.getRawId(particle));
+            particleJson.addProperty("name", Registry.PARTICLE_TYPE.getId(particle).getPath());
+
+            particlesJson.add(particleJson);
+        }
+
+        return particlesJson;
+    }
+}
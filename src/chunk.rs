@@ -11,7 +11,8 @@ use std::io::Write;
 use std::iter::FusedIterator;
 use std::mem;
 use std::ops::{Deref, DerefMut, Index, IndexMut};
-use std::sync::{Mutex, MutexGuard};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard};
 
 use entity_partition::PartitionCell;
 use paletted_container::PalettedContainer;
@@ -47,6 +48,11 @@ pub struct Chunks<C: Config> {
     filler_sky_light_arrays: Box<[LengthPrefixedArray<u8, 2048>]>,
     biome_registry_len: usize,
     compression_threshold: Option<u32>,
+    /// The number of loaded chunks in `chunks`.
+    loaded_count: usize,
+    /// The number of loaded chunks in all worlds on the server. Kept in sync
+    /// with `loaded_count`.
+    server_loaded_count: Arc<AtomicUsize>,
 }
 
 impl<C: Config> Chunks<C> {
@@ -55,6 +61,7 @@ impl<C: Config> Chunks<C> {
         dimension_min_y: i32,
         biome_registry_len: usize,
         compression_threshold: Option<u32>,
+        server_loaded_count: Arc<AtomicUsize>,
     ) -> Self {
         let section_count = (dimension_height / 16 + 2) as usize;
 
@@ -72,6 +79,8 @@ impl<C: Config> Chunks<C> {
             filler_sky_light_arrays: vec![LengthPrefixedArray([0xff; 2048]); section_count].into(),
             biome_registry_len,
             compression_threshold,
+            loaded_count: 0,
+            server_loaded_count,
         }
     }
 
@@ -98,17 +107,37 @@ impl<C: Config> Chunks<C> {
 
         match self.chunks.entry(pos.into()) {
             Entry::Occupied(mut oe) => {
+                if oe.get().0.is_none() {
+                    self.loaded_count += 1;
+                    self.server_loaded_count.fetch_add(1, Ordering::Relaxed);
+                }
+
                 oe.get_mut().0 = Some(loaded);
                 oe.into_mut().0.as_mut().unwrap()
             }
-            Entry::Vacant(ve) => ve
-                .insert((Some(loaded), PartitionCell::new()))
-                .0
-                .as_mut()
-                .unwrap(),
+            Entry::Vacant(ve) => {
+                self.loaded_count += 1;
+                self.server_loaded_count.fetch_add(1, Ordering::Relaxed);
+
+                ve.insert((Some(loaded), PartitionCell::new()))
+                    .0
+                    .as_mut()
+                    .unwrap()
+            }
         }
     }
 
+    /// Returns the number of loaded chunks in the world. Chunks marked as
+    /// deleted are counted until the end of the tick.
+    pub fn len(&self) -> usize {
+        self.loaded_count
+    }
+
+    /// Returns `true` if there are no loaded chunks in the world.
+    pub fn is_empty(&self) -> bool {
+        self.loaded_count == 0
+    }
+
     /// Returns the height of all loaded chunks in the world. This returns the
     /// same value as [`Chunk::height`] for all loaded chunks.
     pub fn height(&self) -> usize {
@@ -237,8 +266,14 @@ impl<C: Config> Chunks<C> {
             return BlockState::AIR;
         }
 
+        let loaded_count = &mut self.loaded_count;
+        let server_loaded_count = &self.server_loaded_count;
+
         let chunk = match self.chunks.entry(ChunkPos::from(pos)) {
             Entry::Occupied(oe) => oe.into_mut().0.get_or_insert_with(|| {
+                *loaded_count += 1;
+                server_loaded_count.fetch_add(1, Ordering::Relaxed);
+
                 let dimension_section_count = (self.dimension_height / 16) as usize;
                 LoadedChunk::new(
                     UnloadedChunk::default(),
@@ -247,6 +282,9 @@ impl<C: Config> Chunks<C> {
                 )
             }),
             Entry::Vacant(ve) => {
+                *loaded_count += 1;
+                server_loaded_count.fetch_add(1, Ordering::Relaxed);
+
                 let dimension_section_count = (self.dimension_height / 16) as usize;
                 let loaded = LoadedChunk::new(
                     UnloadedChunk::default(),
@@ -400,10 +438,13 @@ impl<C: Config> Chunks<C> {
     /// Clears changes to partition cells and removes deleted chunks and
     /// partition cells.
     pub(crate) fn update(&mut self) {
+        let mut removed = 0;
+
         self.chunks.retain(|_, (chunk_opt, cell)| {
             if let Some(chunk) = chunk_opt {
                 if chunk.deleted {
                     *chunk_opt = None;
+                    removed += 1;
                 } else {
                     chunk.created_this_tick = false;
                 }
@@ -413,6 +454,18 @@ impl<C: Config> Chunks<C> {
 
             chunk_opt.is_some() || cell.entities().len() > 0
         });
+
+        self.loaded_count -= removed;
+        self.server_loaded_count
+            .fetch_sub(removed, Ordering::Relaxed);
+    }
+}
+
+impl<C: Config> Drop for Chunks<C> {
+    fn drop(&mut self) {
+        // The chunks of removed worlds are no longer loaded.
+        self.server_loaded_count
+            .fetch_sub(self.loaded_count, Ordering::Relaxed);
     }
 }
 
@@ -1086,4 +1139,33 @@ mod tests {
         check_invariants(&loaded.sections);
         check_invariants(&unloaded.sections);
     }
+
+    #[test]
+    fn set_block_state_counts_created_chunks() {
+        let server_loaded_count = Arc::new(AtomicUsize::new(0));
+        let mut chunks = Chunks::<MockConfig>::new(256, 0, 1, None, server_loaded_count.clone());
+
+        chunks.set_block_state([0, 64, 0], BlockState::STONE);
+        assert_eq!(chunks.len(), 1);
+
+        // A partition cell without a chunk, as left behind by an entity.
+        chunks
+            .chunks
+            .insert(ChunkPos::new(5, 5), (None, PartitionCell::new()));
+        chunks.set_block_state([80, 64, 80], BlockState::STONE);
+        assert_eq!(chunks.len(), 2);
+
+        // Setting a block in an existing chunk does not change the count.
+        chunks.set_block_state([1, 64, 1], BlockState::DIRT);
+        assert_eq!(chunks.len(), 2);
+        assert_eq!(server_loaded_count.load(Ordering::Relaxed), 2);
+
+        chunks.get_mut([0, 0]).unwrap().set_deleted(true);
+        chunks.update();
+        assert_eq!(chunks.len(), 1);
+        assert_eq!(server_loaded_count.load(Ordering::Relaxed), 1);
+
+        drop(chunks);
+        assert_eq!(server_loaded_count.load(Ordering::Relaxed), 0);
+    }
 }
@@ -1,11 +1,17 @@
 use std::collections::hash_map::Entry;
 use std::collections::BTreeSet;
+use std::ops::Range;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use rayon::iter::{IntoParallelRefMutIterator, ParallelIterator};
+use vek::Vec3;
 
 use crate::chunk::ChunkPos;
 use crate::config::Config;
-use crate::entity::{Entities, EntityId};
-use crate::packet::PacketWriter;
+use crate::entity::{Entities, Entity, EntityId};
+use crate::packet::{PacketWriter, WritePacket};
 use crate::world::Worlds;
+use crate::Ticks;
 
 pub struct PartitionCell {
     /// Entities in this cell.
@@ -20,6 +26,25 @@ pub struct PartitionCell {
     outgoing: Vec<(EntityId, Option<ChunkPos>)>,
     /// A cache of packets needed to update all the `entities` in this chunk.
     cached_update_packets: Vec<u8>,
+    /// Like `cached_update_packets`, but without relative movement. Sent to
+    /// clients far enough away for entity movement to be throttled.
+    cached_throttled_update_packets: Vec<u8>,
+    /// A cache of packets needed to spawn all the `entities` in this chunk for
+    /// a client that is loading it. Only built for the cells some client is
+    /// about to load, before the client update procedure.
+    cached_init_packets: InitPacketCache,
+    /// If a client is about to load this cell and the init packets should be
+    /// cached.
+    init_packets_requested: AtomicBool,
+}
+
+#[derive(Default)]
+struct InitPacketCache {
+    /// If `bytes` is up to date for the current tick.
+    valid: bool,
+    bytes: Vec<u8>,
+    /// The range of `bytes` spawning each entity, sorted by entity ID.
+    ranges: Vec<(EntityId, Range<usize>)>,
 }
 
 impl PartitionCell {
@@ -29,6 +54,9 @@ impl PartitionCell {
             incoming: vec![],
             outgoing: vec![],
             cached_update_packets: vec![],
+            cached_throttled_update_packets: vec![],
+            cached_init_packets: InitPacketCache::default(),
+            init_packets_requested: AtomicBool::new(false),
         }
     }
 
@@ -44,22 +72,278 @@ impl PartitionCell {
         &self.outgoing
     }
 
-    pub fn cached_update_packets(&self) -> &[u8] {
-        &self.cached_update_packets
-    }
-
     pub(super) fn clear_incoming_outgoing(&mut self) {
         self.incoming.clear();
         self.outgoing.clear();
     }
+
+    /// Caches the update packets of every entity in this cell and invalidates
+    /// the init packets. The throttled update packets are only cached if
+    /// `throttled_updates` is set.
+    fn cache_update_packets<C: Config>(
+        &mut self,
+        entities: &mut Entities<C>,
+        compression_threshold: Option<u32>,
+        throttled_updates: bool,
+        current_tick: Ticks,
+        scratch: &mut Vec<u8>,
+        compression_scratch: &mut Vec<u8>,
+    ) {
+        self.cached_update_packets.clear();
+        self.cached_throttled_update_packets.clear();
+        self.cached_init_packets.valid = false;
+
+        for &id in &self.entities {
+            let entity = &mut entities[id];
+
+            if entity.moved() {
+                entity.last_moved_tick = current_tick;
+            }
+
+            let start = self.cached_update_packets.len();
+
+            let writer = PacketWriter::new(
+                &mut self.cached_update_packets,
+                compression_threshold,
+                compression_scratch,
+            );
+
+            entity.write_update_packets(writer, id, scratch).unwrap();
+
+            let end = self.cached_update_packets.len();
+            entity.self_update_range = start..end;
+
+            if throttled_updates {
+                let start = self.cached_throttled_update_packets.len();
+
+                let writer = PacketWriter::new(
+                    &mut self.cached_throttled_update_packets,
+                    compression_threshold,
+                    compression_scratch,
+                );
+
+                entity
+                    .write_throttled_update_packets(writer, id, scratch)
+                    .unwrap();
+
+                let end = self.cached_throttled_update_packets.len();
+                entity.self_throttled_update_range = start..end;
+            }
+        }
+    }
+
+    /// Marks this cell as about to be loaded by a client so that its init
+    /// packets are cached by [`cache_entity_init_packets`].
+    pub(crate) fn request_init_packets(&self) {
+        self.init_packets_requested.store(true, Ordering::Relaxed);
+    }
+
+    /// Caches the packets to spawn every entity in this cell.
+    fn cache_init_packets<C: Config>(
+        &mut self,
+        entities: &Entities<C>,
+        compression_threshold: Option<u32>,
+    ) {
+        let InitPacketCache {
+            valid,
+            bytes,
+            ranges,
+        } = &mut self.cached_init_packets;
+
+        bytes.clear();
+        ranges.clear();
+
+        let mut scratch = vec![];
+        let mut compression_scratch = vec![];
+
+        for &id in &self.entities {
+            let entity = &entities[id];
+            let start = bytes.len();
+
+            let mut writer =
+                PacketWriter::new(&mut *bytes, compression_threshold, &mut compression_scratch);
+
+            entity
+                .send_init_packets(&mut writer, entity.position(), id, &mut scratch)
+                .unwrap();
+
+            ranges.push((id, start..bytes.len()));
+        }
+
+        // Passengers are mounted once every entity in the cell is spawned.
+        for &id in &self.entities {
+            let entity = &entities[id];
+
+            if !entity.passengers().is_empty() {
+                let writer =
+                    PacketWriter::new(&mut *bytes, compression_threshold, &mut compression_scratch);
+
+                entity.write_passengers_packet(writer, id).unwrap();
+            }
+        }
+
+        *valid = true;
+    }
+
+    /// Writes the packets to spawn the entities in this cell for a client,
+    /// skipping the entity `exclude`.
+    ///
+    /// The cached packets are used if this cell was requested with
+    /// [`Self::request_init_packets`] before the client update procedure.
+    /// Otherwise, the packets are encoded for this client alone.
+    pub(crate) fn write_init_packets<C: Config>(
+        &self,
+        mut writer: impl WritePacket,
+        entities: &Entities<C>,
+        exclude: EntityId,
+        scratch: &mut Vec<u8>,
+    ) -> anyhow::Result<()> {
+        let cache = &self.cached_init_packets;
+
+        if cache.valid {
+            let range = match cache.ranges.binary_search_by_key(&exclude, |(id, _)| *id) {
+                Ok(idx) => cache.ranges[idx].1.clone(),
+                Err(_) => 0..0,
+            };
+
+            return write_bytes_excluding(writer, &cache.bytes, range);
+        }
+
+        for &id in &self.entities {
+            if id != exclude {
+                let entity = &entities[id];
+                entity.send_init_packets(&mut writer, entity.position(), id, scratch)?;
+            }
+        }
+
+        for &id in &self.entities {
+            let entity = &entities[id];
+
+            if !entity.passengers().is_empty() {
+                entity.write_passengers_packet(&mut writer, id)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Writes the packets to update the entities in this cell for a client,
+    /// skipping the entity `exclude`.
+    ///
+    /// `interval` is the number of ticks between movement updates for this
+    /// cell. `prev_interval` returns the interval a cell had for the client on
+    /// the previous tick. Entities whose movement was held back are teleported
+    /// into place when needed.
+    #[allow(clippy::too_many_arguments)]
+    pub(crate) fn write_update_packets<C: Config>(
+        &self,
+        mut writer: impl WritePacket,
+        entities: &Entities<C>,
+        exclude: EntityId,
+        pos: ChunkPos,
+        current_tick: Ticks,
+        interval: Ticks,
+        prev_interval: impl Fn(ChunkPos) -> Ticks,
+    ) -> anyhow::Result<()> {
+        let excluded = self.entities.contains(&exclude);
+
+        if interval <= 1 {
+            let prev = prev_interval(pos);
+
+            if prev > 1 {
+                // Relative movement is about to resume. Move the entities to
+                // where that movement starts from.
+                self.write_sync_packets(&mut writer, entities, exclude, current_tick, prev, |e| {
+                    e.old_position()
+                })?;
+            } else {
+                // Entities coming from a throttled cell need the same.
+                for &(id, src_pos) in &self.incoming {
+                    if let Some(src_pos) = src_pos {
+                        let prev = prev_interval(src_pos);
+                        let entity = &entities[id];
+
+                        if id != exclude && prev > 1 && current_tick - entity.last_moved_tick < prev
+                        {
+                            entity.write_sync_packets(&mut writer, id, entity.old_position())?;
+                        }
+                    }
+                }
+            }
+
+            let range = if excluded {
+                entities[exclude].self_update_range.clone()
+            } else {
+                0..0
+            };
+
+            write_bytes_excluding(writer, &self.cached_update_packets, range)
+        } else {
+            let prev = prev_interval(pos);
+
+            if prev != interval || current_tick % interval == 0 {
+                let window = interval.max(prev);
+                self.write_sync_packets(
+                    &mut writer,
+                    entities,
+                    exclude,
+                    current_tick,
+                    window,
+                    |e| e.position(),
+                )?;
+            }
+
+            let range = if excluded {
+                entities[exclude].self_throttled_update_range.clone()
+            } else {
+                0..0
+            };
+
+            write_bytes_excluding(writer, &self.cached_throttled_update_packets, range)
+        }
+    }
+
+    /// Teleports every entity in this cell that moved in the last `window`
+    /// ticks to the position returned by `position`.
+    fn write_sync_packets<C: Config>(
+        &self,
+        mut writer: impl WritePacket,
+        entities: &Entities<C>,
+        exclude: EntityId,
+        current_tick: Ticks,
+        window: Ticks,
+        position: impl Fn(&Entity<C>) -> Vec3<f64>,
+    ) -> anyhow::Result<()> {
+        for &id in &self.entities {
+            let entity = &entities[id];
+
+            if id != exclude && current_tick - entity.last_moved_tick < window {
+                entity.write_sync_packets(&mut writer, id, position(entity))?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+fn write_bytes_excluding(
+    mut writer: impl WritePacket,
+    bytes: &[u8],
+    exclude: Range<usize>,
+) -> anyhow::Result<()> {
+    writer.write_bytes(&bytes[..exclude.start])?;
+    writer.write_bytes(&bytes[exclude.end..])
 }
 
 /// Prepares the entity partitions in all worlds for the client update
-/// procedure.
+/// procedure. `throttled_updates` should be set if any entity update tier
+/// sends movement less often than every tick.
 pub fn update_entity_partition<C: Config>(
     entities: &mut Entities<C>,
     worlds: &mut Worlds<C>,
     compression_threshold: Option<u32>,
+    throttled_updates: bool,
+    current_tick: Ticks,
 ) {
     for (entity_id, entity) in entities.iter() {
         let pos = ChunkPos::at(entity.position().x, entity.position().z);
@@ -105,6 +389,9 @@ pub fn update_entity_partition<C: Config>(
                             incoming: vec![(entity_id, None)],
                             outgoing: vec![],
                             cached_update_packets: vec![],
+                            cached_throttled_update_packets: vec![],
+                            cached_init_packets: InitPacketCache::default(),
+                            init_packets_requested: AtomicBool::new(false),
                         };
 
                         ve.insert((None, cell));
@@ -136,6 +423,9 @@ pub fn update_entity_partition<C: Config>(
                             incoming: vec![(entity_id, Some(old_pos))],
                             outgoing: vec![],
                             cached_update_packets: vec![],
+                            cached_throttled_update_packets: vec![],
+                            cached_init_packets: InitPacketCache::default(),
+                            init_packets_requested: AtomicBool::new(false),
                         };
 
                         ve.insert((None, cell));
@@ -154,26 +444,246 @@ pub fn update_entity_partition<C: Config>(
 
     for (_, world) in worlds.iter_mut() {
         for cell in world.chunks.cells_mut() {
-            cell.cached_update_packets.clear();
+            cell.cache_update_packets(
+                entities,
+                compression_threshold,
+                throttled_updates,
+                current_tick,
+                &mut scratch,
+                &mut compression_scratch,
+            );
+        }
+    }
+}
+
+/// Caches the init packets of the partition cells requested with
+/// [`PartitionCell::request_init_packets`] since the last call.
+///
+/// This is called after [`update_entity_partition`] and before the client
+/// update procedure so that clients loading the same cells share the cached
+/// packets without locking.
+pub fn cache_entity_init_packets<C: Config>(
+    entities: &Entities<C>,
+    worlds: &mut Worlds<C>,
+    compression_threshold: Option<u32>,
+) {
+    for (_, world) in worlds.iter_mut() {
+        world
+            .chunks
+            .chunks
+            .par_iter_mut()
+            .for_each(|(_, (_, cell))| {
+                if cell.init_packets_requested.swap(false, Ordering::Relaxed) {
+                    cell.cache_init_packets(entities, compression_threshold);
+                }
+            });
+    }
+}
 
-            for &id in &cell.entities {
-                let start = cell.cached_update_packets.len();
+/// Internals exposed for the `bench_entities` benchmark. Not part of the
+/// public API.
+#[cfg(feature = "bench")]
+#[doc(hidden)]
+pub mod bench {
+    use super::*;
+    use crate::entity::EntityKind;
 
-                let writer = PacketWriter::new(
-                    &mut cell.cached_update_packets,
-                    compression_threshold,
-                    &mut compression_scratch,
-                );
+    /// A partition cell filled with entities, loaded by new viewers with and
+    /// without the init packet cache.
+    pub struct InitPacketsBench<C: Config> {
+        entities: Entities<C>,
+        cell: PartitionCell,
+        compression_threshold: Option<u32>,
+        scratch: Vec<u8>,
+        compression_scratch: Vec<u8>,
+    }
 
-                let entity = &mut entities[id];
+    impl<C: Config> InitPacketsBench<C>
+    where
+        C::EntityState: Default,
+    {
+        /// Creates a cell with `count` entities of the given kind.
+        pub fn new(kind: EntityKind, count: usize, compression_threshold: Option<u32>) -> Self {
+            let mut entities = Entities::new();
+            let mut cell = PartitionCell::new();
 
-                entity
-                    .write_update_packets(writer, id, &mut scratch)
-                    .unwrap();
+            for i in 0..count {
+                let (id, entity) = entities.insert(kind, C::EntityState::default());
+                entity.set_position([(i % 16) as f64, 0.0, (i / 16 % 16) as f64]);
+                cell.entities.insert(id);
+            }
+
+            Self {
+                entities,
+                cell,
+                compression_threshold,
+                scratch: vec![],
+                compression_scratch: vec![],
+            }
+        }
+
+        /// Writes the init packets of every entity for one viewer, encoding
+        /// them for that viewer alone.
+        pub fn write_uncached(&mut self, out: &mut Vec<u8>) {
+            self.cell.cached_init_packets.valid = false;
+            self.write(out);
+        }
+
+        /// Builds the init packet cache as [`cache_entity_init_packets`] does
+        /// for a requested cell.
+        pub fn build_cache(&mut self) {
+            self.cell
+                .cache_init_packets(&self.entities, self.compression_threshold);
+        }
+
+        /// Writes the init packets of every entity for one viewer from the
+        /// cache built with [`Self::build_cache`].
+        pub fn write_cached(&mut self, out: &mut Vec<u8>) {
+            assert!(self.cell.cached_init_packets.valid, "cache not built");
+            self.write(out);
+        }
+
+        fn write(&mut self, out: &mut Vec<u8>) {
+            let writer = PacketWriter::new(
+                out,
+                self.compression_threshold,
+                &mut self.compression_scratch,
+            );
 
-                let end = cell.cached_update_packets.len();
-                entity.self_update_range = start..end;
+            self.cell
+                .write_init_packets(writer, &self.entities, EntityId::NULL, &mut self.scratch)
+                .unwrap();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use valence_protocol::packets::S2cPlayPacket;
+
+    use super::*;
+    use crate::entity::EntityKind;
+    use crate::packet::decode_packets;
+
+    type MockConfig = crate::config::MockConfig;
+
+    /// Moves an entity by `step` blocks every tick and counts the relative
+    /// movement and teleport packets a client sees with the given movement
+    /// update interval.
+    fn count_movement_packets(interval: Ticks, ticks: Ticks, step: f64) -> (usize, usize) {
+        let mut entities = Entities::<MockConfig>::new();
+        let (id, _) = entities.insert(EntityKind::Zombie, ());
+
+        let mut cell = PartitionCell::new();
+        cell.entities.insert(id);
+
+        let mut scratch = vec![];
+        let mut compression_scratch = vec![];
+        let mut moves = 0;
+        let mut teleports = 0;
+
+        for tick in 1..=ticks {
+            let pos = entities[id].position() + Vec3::new(step, 0.0, 0.0);
+            entities[id].set_position(pos);
+
+            cell.cache_update_packets(
+                &mut entities,
+                None,
+                interval > 1,
+                tick,
+                &mut scratch,
+                &mut compression_scratch,
+            );
+
+            let mut bytes = vec![];
+            cell.write_update_packets(
+                PacketWriter::new(&mut bytes, None, &mut compression_scratch),
+                &entities,
+                EntityId::NULL,
+                ChunkPos::new(0, 0),
+                tick,
+                interval,
+                |_| interval,
+            )
+            .unwrap();
+
+            for pkt in decode_packets(&bytes) {
+                match pkt {
+                    S2cPlayPacket::UpdateEntityPosition(_) => moves += 1,
+                    S2cPlayPacket::TeleportEntity(_) => teleports += 1,
+                    _ => {}
+                }
             }
+
+            entities.update();
+        }
+
+        (moves, teleports)
+    }
+
+    #[test]
+    fn near_entity_movement_sent_every_tick() {
+        assert_eq!(count_movement_packets(1, 8, 0.5), (8, 0));
+    }
+
+    #[test]
+    fn far_entity_movement_sent_at_reduced_cadence() {
+        // Synced with a teleport on ticks 4 and 8 only.
+        assert_eq!(count_movement_packets(4, 8, 0.5), (0, 2));
+    }
+
+    #[test]
+    fn far_entity_teleport_not_throttled() {
+        assert_eq!(count_movement_packets(4, 1, 10.0), (0, 1));
+    }
+
+    #[test]
+    fn throttled_updates_not_cached_without_tiers() {
+        let mut entities = Entities::<MockConfig>::new();
+        let (id, entity) = entities.insert(EntityKind::Zombie, ());
+        entity.set_position([1.0, 0.0, 0.0]);
+
+        let mut cell = PartitionCell::new();
+        cell.entities.insert(id);
+
+        cell.cache_update_packets(&mut entities, None, false, 1, &mut vec![], &mut vec![]);
+
+        assert!(!cell.cached_update_packets.is_empty());
+        assert!(cell.cached_throttled_update_packets.is_empty());
+    }
+
+    #[test]
+    fn cached_init_packets_match_uncached() {
+        let mut entities = Entities::<MockConfig>::new();
+        let mut cell = PartitionCell::new();
+
+        for i in 0..3 {
+            let (id, entity) = entities.insert(EntityKind::Zombie, ());
+            entity.set_position([i as f64, 0.0, 0.0]);
+            cell.entities.insert(id);
         }
+
+        let exclude = *cell.entities.iter().nth(1).unwrap();
+
+        let write = |cell: &PartitionCell| {
+            let mut bytes = vec![];
+            let mut compression_scratch = vec![];
+            cell.write_init_packets(
+                PacketWriter::new(&mut bytes, None, &mut compression_scratch),
+                &entities,
+                exclude,
+                &mut vec![],
+            )
+            .unwrap();
+            bytes
+        };
+
+        let uncached = write(&cell);
+
+        cell.cache_init_packets(&entities, None);
+        assert!(cell.cached_init_packets.valid);
+
+        assert_eq!(write(&cell), uncached);
+        assert_eq!(decode_packets(&uncached).len(), 2);
     }
 }
@@ -38,6 +38,15 @@ impl ChunkPos {
         diff_x * diff_x + diff_z * diff_z <= dist * dist
     }
 
+    /// Returns the larger of the X and Z distances between two chunk
+    /// positions, saturating at `u8::MAX`.
+    pub(crate) fn distance(self, other: Self) -> u8 {
+        let diff_x = (other.x as i64 - self.x as i64).abs();
+        let diff_z = (other.z as i64 - self.z as i64).abs();
+
+        diff_x.max(diff_z).min(u8::MAX as i64) as u8
+    }
+
     /// Returns an iterator over all chunk positions within a view distance
     /// centered on `self`. The `self` position is included in the output.
     pub fn in_view(self, view_dist: u8) -> impl FusedIterator<Item = Self> {
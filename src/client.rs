@@ -1,5 +1,6 @@
 //! Connections to the server after logging in.
 
+use std::collections::{HashSet, VecDeque};
 use std::iter::FusedIterator;
 use std::net::IpAddr;
 use std::num::Wrapping;
@@ -8,26 +9,31 @@ use std::{array, fmt, mem};
 
 use anyhow::{bail, ensure, Context};
 pub use bitfield_struct::bitfield;
-pub use event::ClientEvent;
+pub use boss_bar::{BossBar, BossBarColor, BossBarDivision, BossBarFlags};
+pub use event::{ClientEvent, MAX_PLUGIN_MESSAGE_LEN};
 use rayon::iter::ParallelIterator;
 use tokio::sync::OwnedSemaphorePermit;
 use tracing::{info, warn};
 use uuid::Uuid;
+use valence_nbt::Compound;
 use valence_protocol::packets::s2c::play::{
-    AcknowledgeBlockChange, ClearTitles, CombatDeath, CustomSoundEffect, DisconnectPlay,
-    EntityAnimationS2c, EntityEvent, GameEvent, KeepAliveS2c, LoginPlayOwned, OpenScreen,
-    PluginMessageS2c, RemoveEntitiesEncode, ResourcePackS2c, RespawnOwned, SetActionBarText,
-    SetCenterChunk, SetContainerContentEncode, SetContainerSlotEncode, SetDefaultSpawnPosition,
+    AcknowledgeBlockChange, BossBar as BossBarPacket, ClearTitles, CloseContainerS2c, CombatDeath,
+    CustomSoundEffect, DisconnectPlay, EntityAnimationS2c, EntityEvent, GameEvent, KeepAliveS2c,
+    LoginPlayOwned, OpenScreen, ParticleS2c, PlayerAbilitiesS2c, PluginMessageS2c,
+    RemoveEntitiesEncode, ResourcePackS2c, RespawnOwned, SetActionBarText, SetCenterChunk,
+    SetContainerContentEncode, SetContainerSlotEncode, SetCooldown, SetDefaultSpawnPosition,
     SetEntityMetadata, SetEntityVelocity, SetExperience, SetHealth, SetRenderDistance,
-    SetSubtitleText, SetTitleAnimationTimes, SetTitleText, SynchronizePlayerPosition,
-    SystemChatMessage, UnloadChunk, UpdateAttributes, UpdateTime,
+    SetSimulationDistance, SetSubtitleText, SetTitleAnimationTimes, SetTitleText, SoundEffect,
+    StopSound, SynchronizePlayerPosition, SystemChatMessage, UnloadChunk, UpdateAttributes,
+    UpdateTime,
 };
 use valence_protocol::types::{
-    AttributeProperty, DisplayedSkinParts, GameMode, GameStateChangeReason, SoundCategory,
+    AttributeProperty, BossBarAction, DisplayedSkinParts, EquipmentSlot, GameMode,
+    GameStateChangeReason, Hand, PlayerAbilitiesFlags, SoundCategory, StopSoundFilter,
     SyncPlayerPosLookFlags,
 };
 use valence_protocol::{
-    BlockPos, Encode, Ident, ItemStack, Packet, RawBytes, Text, Username, VarInt,
+    BlockPos, Encode, Ident, ItemKind, ItemStack, Packet, RawBytes, Text, Username, VarInt,
 };
 use vek::Vec3;
 
@@ -35,16 +41,20 @@ use crate::chunk::ChunkPos;
 use crate::client::event::next_event_fallible;
 use crate::config::Config;
 use crate::dimension::DimensionId;
-use crate::entity::data::Player;
+pub use crate::entity::data::PlayerDataField;
+use crate::entity::data::{Player, TrackedData};
 use crate::entity::{self, velocity_to_packet_units, Entities, EntityId, StatusOrAnimation};
-use crate::inventory::{Inventories, InventoryId};
+use crate::inventory::{Inventories, InventoryId, PlayerInventorySlot};
+use crate::particle::Particle;
 use crate::player_list::{PlayerListId, PlayerLists};
 use crate::player_textures::SignedPlayerTextures;
 use crate::server::{NewClientData, PlayPacketReceiver, PlayPacketSender, SharedServer};
 use crate::slab_versioned::{Key, VersionedSlab};
+use crate::util::{note_pitch, xp_at_level};
 use crate::world::{WorldId, Worlds};
 use crate::Ticks;
 
+mod boss_bar;
 mod event;
 
 /// A container for all [`Client`]s on a [`Server`](crate::server::Server).
@@ -54,12 +64,20 @@ mod event;
 /// they disconnect. This can be checked with [`Client::is_disconnected`].
 pub struct Clients<C: Config> {
     slab: VersionedSlab<Client<C>>,
+    /// The automatically managed player list entries of removed clients
+    /// which have yet to be removed from their player list.
+    removed_list_entries: Vec<(Uuid, PlayerListId)>,
+    /// The removed clients which have yet to be passed to
+    /// [`Config::on_client_leave`].
+    removed_unnotified: Vec<ClientId>,
 }
 
 impl<C: Config> Clients<C> {
     pub(crate) fn new() -> Self {
         Self {
             slab: VersionedSlab::new(),
+            removed_list_entries: vec![],
+            removed_unnotified: vec![],
         }
     }
 
@@ -76,6 +94,15 @@ impl<C: Config> Clients<C> {
     pub fn remove(&mut self, client: ClientId) -> Option<C::ClientState> {
         self.slab.remove(client.0).map(|c| {
             info!(username = %c.username, uuid = %c.uuid, ip = %c.ip, "removing client");
+
+            if let Some(id) = c.list_entry {
+                self.removed_list_entries.push((c.uuid, id));
+            }
+
+            if !c.bits.leave_notified() {
+                self.removed_unnotified.push(client);
+            }
+
             c.state
         })
     }
@@ -84,9 +111,21 @@ impl<C: Config> Clients<C> {
     ///
     /// All clients are visited in an unspecified order.
     pub fn retain(&mut self, mut f: impl FnMut(ClientId, &mut Client<C>) -> bool) {
+        let removed_list_entries = &mut self.removed_list_entries;
+        let removed_unnotified = &mut self.removed_unnotified;
+
         self.slab.retain(|k, v| {
             if !f(ClientId(k), v) {
                 info!(username = %v.username, uuid = %v.uuid, ip = %v.ip, "removing client");
+
+                if let Some(id) = v.list_entry.take() {
+                    removed_list_entries.push((v.uuid, id));
+                }
+
+                if !v.bits.leave_notified() {
+                    removed_unnotified.push(ClientId(k));
+                }
+
                 false
             } else {
                 true
@@ -94,6 +133,53 @@ impl<C: Config> Clients<C> {
         })
     }
 
+    /// Returns the IDs of the clients removed since the last call which have
+    /// not been passed to [`Config::on_client_leave`].
+    pub(crate) fn take_removed_unnotified(&mut self) -> Vec<ClientId> {
+        mem::take(&mut self.removed_unnotified)
+    }
+
+    /// Adds every connected client to the player list it is viewing and
+    /// removes the entries of clients which disconnected, were removed, or
+    /// switched player lists. Used when
+    /// [`Config::auto_manage_player_list`] is enabled.
+    pub(crate) fn update_player_list_entries(&mut self, player_lists: &mut PlayerLists<C>) {
+        for (uuid, id) in self.removed_list_entries.drain(..) {
+            player_lists.get_mut(&id).remove(uuid);
+        }
+
+        for (_, client) in self.slab.iter_mut() {
+            let list = if client.is_disconnected() {
+                None
+            } else {
+                client.player_list.clone()
+            };
+
+            if client.list_entry != list {
+                if let Some(old) = client.list_entry.take() {
+                    player_lists.get_mut(&old).remove(client.uuid);
+                }
+
+                if let Some(new) = &list {
+                    player_lists.get_mut(new).insert(
+                        client.uuid,
+                        client.username.as_str(),
+                        client.textures.clone(),
+                        client.game_mode,
+                        0,
+                        None,
+                    );
+                }
+
+                client.list_entry = list;
+            } else if let Some(id) = &client.list_entry {
+                if let Some(entry) = player_lists.get_mut(id).entry_mut(client.uuid) {
+                    entry.set_game_mode(client.game_mode);
+                }
+            }
+        }
+    }
+
     /// Returns the number of clients on the server. This includes clients for
     /// which [`Client::is_disconnected`] returns true.
     pub fn len(&self) -> usize {
@@ -178,7 +264,8 @@ impl ClientId {
 ///
 /// Additionally, clients possess [`Player`] entity data which is only visible
 /// to themselves. This can be accessed with [`Self::player`] and
-/// [`Self::player_mut`].
+/// [`Self::player_mut`]. Individual fields can be made visible to others with
+/// [`Self::set_player_field_broadcast`].
 ///
 /// # The Difference Between a "Client" and a "Player"
 ///
@@ -198,6 +285,10 @@ pub struct Client<C: Config> {
     /// unloaded chunks.
     #[cfg(debug_assertions)]
     loaded_chunks: std::collections::HashSet<ChunkPos>,
+    /// The number of chunks loaded during the last update.
+    chunks_loaded_this_tick: u32,
+    /// The number of chunks unloaded during the last update.
+    chunks_unloaded_this_tick: u32,
     /// Ensures that we don't allow more connections to the server until the
     /// client is dropped.
     _permit: OwnedSemaphorePermit,
@@ -211,6 +302,11 @@ pub struct Client<C: Config> {
     uuid: Uuid,
     ip: IpAddr,
     textures: Option<SignedPlayerTextures>,
+    /// The registry codec to send in the login packet instead of the server's.
+    /// Taken when the login packet is sent.
+    registry_codec: Option<Compound>,
+    /// The tick the client was inserted into the server.
+    created_tick: Ticks,
     /// World client is currently in. Default value is **invalid** and must
     /// be set by calling [`Client::respawn`].
     world: WorldId,
@@ -218,15 +314,28 @@ pub struct Client<C: Config> {
     player_list: Option<PlayerListId>,
     /// Player list from the previous tick.
     old_player_list: Option<PlayerListId>,
+    /// The player list this client was automatically added to.
+    list_entry: Option<PlayerListId>,
     position: Vec3<f64>,
     /// Position from the previous tick.
     old_position: Vec3<f64>,
+    /// The tick and position at the end of the most recent updates, oldest
+    /// first. Holds at most [`POSITION_HISTORY_LEN`] entries.
+    position_history: VecDeque<(Ticks, Vec3<f64>)>,
+    /// The chunk entity update intervals were measured from on the previous
+    /// tick.
+    entity_update_center: ChunkPos,
     /// Measured in degrees
     yaw: f32,
     /// Measured in degrees
     pitch: f32,
     view_distance: u8,
     old_view_distance: u8,
+    /// The entity view distance requested with `set_entity_view_distance`.
+    entity_view_distance: u8,
+    old_entity_view_distance: u8,
+    simulation_distance: u8,
+    old_simulation_distance: u8,
     /// Counts up as teleports are made.
     teleport_id_counter: u32,
     /// The number of pending client teleports that have yet to receive a
@@ -234,14 +343,28 @@ pub struct Client<C: Config> {
     /// this is nonzero.
     pending_teleports: u32,
     death_location: Option<(DimensionId, BlockPos)>,
+    /// The position and yaw set with `set_spawn_position`.
+    spawn_position: (BlockPos, f32),
+    /// The title of the boss bar shown while a resource pack downloads.
+    resource_pack_bar_title: Option<Text>,
+    /// The ID of the resource pack boss bar, if it is currently shown.
+    resource_pack_bar: Option<Uuid>,
+    /// The IDs of the boss bars shown with [`Client::add_boss_bar`].
+    boss_bars: HashSet<Uuid>,
     /// The ID of the last keepalive sent.
     last_keepalive_id: u64,
+    /// The number of keepalive intervals that have passed in a row without a
+    /// response to the last keepalive.
+    missed_keepalives: u32,
     game_mode: GameMode,
     block_change_sequence: i32,
     /// The data for the client's own player entity.
     player_data: Player,
+    /// Contains a set bit for each [`PlayerDataField`] of `player_data` which
+    /// is copied to the player entity with the same UUID.
+    broadcast_player_fields: u64,
     /// The client's inventory slots.
-    slots: Box<[Option<ItemStack>; 45]>,
+    slots: Box<[Option<ItemStack>; PLAYER_INVENTORY_SLOT_COUNT]>,
     /// Contains a set bit for each modified slot in `slots` made by the server
     /// this tick.
     modified_slots: u64,
@@ -255,10 +378,12 @@ pub struct Client<C: Config> {
     open_inventory: InventoryId,
     /// The current window ID. Incremented when inventories are opened.
     window_id: u8,
+    /// The selected hotbar slot in `0..9`.
+    selected_slot: u8,
     bits: ClientBits,
 }
 
-#[bitfield(u8)]
+#[bitfield(u32)]
 struct ClientBits {
     created_this_tick: bool,
     respawn: bool,
@@ -269,8 +394,26 @@ struct ClientBits {
     respawn_screen: bool,
     cursor_item_modified: bool,
     open_inventory_modified: bool,
-    //#[bits(1)]
-    //_pad: u8,
+    /// If the item in the selected hotbar slot or the selected slot itself
+    /// changed this tick.
+    held_item_modified: bool,
+    /// If [`Config::on_client_leave`] was called for this client.
+    leave_notified: bool,
+    limbo: bool,
+    /// If the client was in limbo on the previous tick.
+    old_limbo: bool,
+    auto_swap_hands: bool,
+    /// If the window with `window_id` is open on the client.
+    window_open: bool,
+    auto_drop_items: bool,
+    auto_respawn: bool,
+    /// If the packet queue is not flushed at the end of the tick.
+    defer_flush: bool,
+    /// If the client's position must be sent after the login or respawn
+    /// packet.
+    spawn_teleport: bool,
+    #[bits(14)]
+    _pad: u16,
 }
 
 impl<C: Config> Deref for Client<C> {
@@ -293,6 +436,8 @@ impl<C: Config> Client<C> {
         recv: PlayPacketReceiver,
         permit: OwnedSemaphorePermit,
         ncd: NewClientData,
+        registry_codec: Option<Compound>,
+        created_tick: Ticks,
         state: C::ClientState,
     ) -> Self {
         Self {
@@ -301,6 +446,8 @@ impl<C: Config> Client<C> {
             recv,
             #[cfg(debug_assertions)]
             loaded_chunks: Default::default(),
+            chunks_loaded_this_tick: 0,
+            chunks_unloaded_this_tick: 0,
             _permit: permit,
             scratch: vec![],
             entities_to_unload: vec![],
@@ -309,29 +456,45 @@ impl<C: Config> Client<C> {
             uuid: ncd.uuid,
             ip: ncd.ip,
             textures: ncd.textures,
+            registry_codec,
+            created_tick,
             world: WorldId::NULL,
             old_world: WorldId::NULL,
             player_list: None,
             old_player_list: None,
+            list_entry: None,
             position: Vec3::default(),
             old_position: Vec3::default(),
+            position_history: VecDeque::with_capacity(POSITION_HISTORY_LEN),
+            entity_update_center: ChunkPos::new(0, 0),
             yaw: 0.0,
             pitch: 0.0,
             view_distance: 2,
             old_view_distance: 2,
+            entity_view_distance: u8::MAX,
+            old_entity_view_distance: 2,
+            simulation_distance: 16,
+            old_simulation_distance: 16,
             teleport_id_counter: 0,
             pending_teleports: 0,
             death_location: None,
+            spawn_position: (BlockPos::new(0, 0, 0), 0.0),
+            resource_pack_bar_title: None,
+            resource_pack_bar: None,
+            boss_bars: HashSet::new(),
             last_keepalive_id: 0,
+            missed_keepalives: 0,
             game_mode: GameMode::Survival,
             block_change_sequence: 0,
             player_data: Player::new(),
+            broadcast_player_fields: 0,
             slots: Box::new(array::from_fn(|_| None)),
             modified_slots: 0,
             inv_state_id: Wrapping(0),
             cursor_item: None,
             open_inventory: InventoryId::NULL,
             window_id: 0,
+            selected_slot: 0,
             bits: ClientBits::new()
                 .with_got_keepalive(true)
                 .with_created_this_tick(true),
@@ -364,6 +527,15 @@ impl<C: Config> Client<C> {
         self.bits.created_this_tick()
     }
 
+    /// Gets the tick the client joined the game on.
+    ///
+    /// This can be compared against
+    /// [`Server::current_tick`](crate::server::Server::current_tick) to find
+    /// how long the client has been connected.
+    pub fn created_tick(&self) -> Ticks {
+        self.created_tick
+    }
+
     /// Gets the username of this client.
     pub fn username(&self) -> Username<&str> {
         self.username.as_str_username()
@@ -397,11 +569,68 @@ impl<C: Config> Client<C> {
 
     /// Sets the player list this client sees.
     ///
+    /// If `None`, the client sees no player list at all and no player list
+    /// packets will be sent to it. Clients are created without a player list,
+    /// so this is the default.
+    ///
+    /// Note that the client only loads the skins of [`Player`] entities which
+    /// have an entry in the player list it sees. Without one, player entities
+    /// are invisible to the client. To show player entities while hiding the
+    /// tab list, the entries can be added and then removed after the entities
+    /// are loaded.
+    ///
     /// The previous player list ID is returned.
+    ///
+    /// [`Player`]: crate::entity::data::Player
     pub fn set_player_list(&mut self, id: impl Into<Option<PlayerListId>>) -> Option<PlayerListId> {
         mem::replace(&mut self.player_list, id.into())
     }
 
+    /// Immediately sends every packet queued for this client so far.
+    ///
+    /// The packet queue is flushed at the end of every tick, so this is only
+    /// needed when flushing is deferred with [`Self::set_defer_flush`]. If
+    /// flushing fails, the client is disconnected.
+    ///
+    /// This has no effect until the client's first update has run, because
+    /// the login packet must be sent before anything else.
+    pub fn flush_packets(&mut self) {
+        if self.created_this_tick() {
+            return;
+        }
+
+        if let Some(send) = &mut self.send {
+            if let Err(e) = send.flush() {
+                warn!(
+                    username = %self.username,
+                    uuid = %self.uuid,
+                    ip = %self.ip,
+                    "failed to flush packet queue: {e:#}"
+                );
+                self.send = None;
+            }
+        }
+    }
+
+    /// Sets whether the packet queue is left unflushed at the end of the tick.
+    /// While enabled, packets are only sent when [`Self::flush_packets`] is
+    /// called, which allows batching the packets of several ticks together.
+    /// Disabled by default.
+    ///
+    /// **Note**: the queue keeps growing until it is flushed, and flushing more
+    /// data than the configured
+    /// [outgoing capacity](crate::config::Config::outgoing_capacity) at once
+    /// disconnects the client. Avoid deferring for more than a few ticks.
+    pub fn set_defer_flush(&mut self, defer: bool) {
+        self.bits.set_defer_flush(defer);
+    }
+
+    /// If flushing the packet queue is deferred. See
+    /// [`Self::set_defer_flush`].
+    pub fn defer_flush(&self) -> bool {
+        self.bits.defer_flush()
+    }
+
     /// Sets if this client sees the world as superflat. Superflat worlds have
     /// a horizon line lower than normal worlds.
     ///
@@ -419,13 +648,66 @@ impl<C: Config> Client<C> {
     /// Changes the world this client is located in and respawns the client.
     /// This can be used to respawn the client after death.
     ///
-    /// The given [`WorldId`] must be valid. Otherwise, the client is
-    /// disconnected.
+    /// If the given [`WorldId`] is invalid, refers to a deleted world, or
+    /// refers to a world whose dimension is not registered with the server,
+    /// the respawn is refused and the client stays in its current world. If
+    /// the client has no current world because it joined this tick, it is
+    /// disconnected instead.
     pub fn respawn(&mut self, world: WorldId) {
         self.world = world;
         self.bits.set_respawn(true);
     }
 
+    /// Respawns the client in `world` at the given position, rotation, and
+    /// game mode in a single call.
+    ///
+    /// This is the recommended way to set up a client on the tick it joins.
+    /// The position is sent after the login packet at the end of the tick, so
+    /// the order of calls made while the client joins does not matter. On
+    /// later ticks, this behaves like [`Self::respawn`] followed by
+    /// [`Self::set_game_mode`] and [`Self::teleport`].
+    pub fn spawn_at(
+        &mut self,
+        world: WorldId,
+        pos: impl Into<Vec3<f64>>,
+        yaw: f32,
+        pitch: f32,
+        game_mode: GameMode,
+    ) {
+        self.respawn(world);
+        self.set_game_mode(game_mode);
+        self.position = pos.into();
+        self.yaw = yaw;
+        self.pitch = pitch;
+        self.bits.set_spawn_teleport(true);
+    }
+
+    /// Puts the client in limbo.
+    ///
+    /// While in limbo, all chunks and entities visible to the client are
+    /// unloaded and no new ones are sent, but the connection is otherwise kept
+    /// alive. This is useful for holding a client that is about to be
+    /// transferred to another server without streaming a world to it. The
+    /// client's world must still be valid.
+    ///
+    /// Has no effect if the client is already in limbo.
+    pub fn enter_limbo(&mut self) {
+        self.bits.set_limbo(true);
+    }
+
+    /// Takes the client out of limbo, sending it the chunks and entities in
+    /// view again.
+    ///
+    /// Has no effect if the client is not in limbo.
+    pub fn leave_limbo(&mut self) {
+        self.bits.set_limbo(false);
+    }
+
+    /// If the client is in limbo. See [`Self::enter_limbo`].
+    pub fn in_limbo(&self) -> bool {
+        self.bits.limbo()
+    }
+
     /// Sends a system message to the player which is visible in the chat. The
     /// message is only visible to this client.
     pub fn send_message(&mut self, msg: impl Into<Text>) {
@@ -448,26 +730,106 @@ impl<C: Config> Client<C> {
         self.position
     }
 
+    /// Returns the position of this client at the end of each of the last
+    /// [`POSITION_HISTORY_LEN`] ticks along with the tick it was recorded on,
+    /// from oldest to newest.
+    ///
+    /// This can be used to rewind the client to where it was at an earlier
+    /// tick, such as when compensating for an attacker's latency.
+    pub fn position_history(
+        &self,
+    ) -> impl ExactSizeIterator<Item = (Ticks, Vec3<f64>)> + DoubleEndedIterator + '_ {
+        self.position_history.iter().copied()
+    }
+
     /// Changes the position and rotation of this client in the world it is
     /// located in.
     ///
+    /// The position and rotation are sent as absolute values, which makes the
+    /// client discard its current velocity. A client teleported while falling
+    /// therefore does not keep its downward momentum. Note that the server is
+    /// responsible for fall damage, so any fall distance tracked by your own
+    /// code should be reset along with the teleport.
+    ///
     /// If you want to change the client's world, use [`Self::respawn`].
     pub fn teleport(&mut self, pos: impl Into<Vec3<f64>>, yaw: f32, pitch: f32) {
         self.position = pos.into();
         self.yaw = yaw;
         self.pitch = pitch;
 
-        self.queue_packet(&SynchronizePlayerPosition {
-            position: self.position.into_array(),
+        let pkt = self.next_teleport_packet();
+        self.queue_packet(&pkt);
+    }
+
+    /// Moves and rotates this client relative to its current position and
+    /// rotation.
+    ///
+    /// Each component marked in `flags` is added to the client's current
+    /// value, while the other components are replaced like in
+    /// [`Self::teleport`]. The client keeps its velocity along the relative
+    /// axes, which makes this suitable for small corrections and nudges.
+    ///
+    /// The `y_rot` flag applies to the yaw and the `x_rot` flag applies to the
+    /// pitch.
+    pub fn teleport_relative(
+        &mut self,
+        delta: impl Into<Vec3<f64>>,
+        yaw_delta: f32,
+        pitch_delta: f32,
+        flags: SyncPlayerPosLookFlags,
+    ) {
+        let delta = delta.into();
+
+        let apply = |relative: bool, old: f64, new: f64| if relative { old + new } else { new };
+
+        self.position = Vec3::new(
+            apply(flags.x(), self.position.x, delta.x),
+            apply(flags.y(), self.position.y, delta.y),
+            apply(flags.z(), self.position.z, delta.z),
+        );
+        self.yaw = apply(flags.y_rot(), self.yaw as f64, yaw_delta as f64) as f32;
+        self.pitch = apply(flags.x_rot(), self.pitch as f64, pitch_delta as f64) as f32;
+
+        let pkt = self.next_teleport_packet_with(delta, yaw_delta, pitch_delta, flags);
+        self.queue_packet(&pkt);
+    }
+
+    /// Returns the packet to move the client to its current position and
+    /// rotation. The teleport is counted as pending until the client confirms
+    /// it.
+    fn next_teleport_packet(&mut self) -> SynchronizePlayerPosition {
+        // No relative flags are set. Absolute coordinates also reset the
+        // client's velocity on each axis.
+        self.next_teleport_packet_with(
+            self.position,
+            self.yaw,
+            self.pitch,
+            SyncPlayerPosLookFlags::new(),
+        )
+    }
+
+    /// Like [`Self::next_teleport_packet`], but the components marked in
+    /// `flags` are sent as offsets from the client's position and rotation.
+    fn next_teleport_packet_with(
+        &mut self,
+        position: Vec3<f64>,
+        yaw: f32,
+        pitch: f32,
+        flags: SyncPlayerPosLookFlags,
+    ) -> SynchronizePlayerPosition {
+        let pkt = SynchronizePlayerPosition {
+            position: position.into_array(),
             yaw,
             pitch,
-            flags: SyncPlayerPosLookFlags::new(),
+            flags,
             teleport_id: VarInt(self.teleport_id_counter as i32),
             dismount_vehicle: false,
-        });
+        };
 
         self.pending_teleports = self.pending_teleports.wrapping_add(1);
         self.teleport_id_counter = self.teleport_id_counter.wrapping_add(1);
+
+        pkt
     }
 
     /// Sets the client's velocity in m/s.
@@ -478,6 +840,41 @@ impl<C: Config> Client<C> {
         })
     }
 
+    /// Sets the velocity of another entity in m/s as seen by this client only,
+    /// such as for client-predicted knockback on a mob. The entity's actual
+    /// velocity is not changed.
+    ///
+    /// Returns `false` without sending anything if the entity does not exist,
+    /// is this client's own player entity, or is not within the client's
+    /// [entity view distance](Self::entity_view_distance) in the same world.
+    pub fn set_entity_velocity(
+        &mut self,
+        entities: &Entities<C>,
+        entity: EntityId,
+        velocity: impl Into<Vec3<f32>>,
+    ) -> bool {
+        let Some(e) = entities.get(entity) else {
+            return false;
+        };
+
+        let chunk_pos = ChunkPos::at(self.position.x, self.position.z);
+        let entity_chunk_pos = ChunkPos::at(e.position().x, e.position().z);
+
+        if e.uuid() == self.uuid
+            || e.world() != self.world
+            || !chunk_pos.is_in_view(entity_chunk_pos, self.entity_view_distance())
+        {
+            return false;
+        }
+
+        self.queue_packet(&SetEntityVelocity {
+            entity_id: VarInt(entity.to_raw()),
+            velocity: velocity_to_packet_units(velocity.into()).into_array(),
+        });
+
+        true
+    }
+
     /// Gets this client's yaw.
     pub fn yaw(&self) -> f32 {
         self.yaw
@@ -490,13 +887,26 @@ impl<C: Config> Client<C> {
 
     /// Sets the spawn position. The client will see `minecraft:compass` items
     /// point at the provided position.
+    ///
+    /// This is also where the client is moved to when it is respawned
+    /// automatically. See [`Self::set_auto_respawn`].
     pub fn set_spawn_position(&mut self, pos: impl Into<BlockPos>, yaw_degrees: f32) {
+        let pos = pos.into();
+        self.spawn_position = (pos, yaw_degrees);
+
         self.queue_packet(&SetDefaultSpawnPosition {
-            position: pos.into(),
+            position: pos,
             angle: yaw_degrees,
         });
     }
 
+    /// Gets the spawn position and yaw last set with
+    /// [`Self::set_spawn_position`]. The default is the origin with a yaw of
+    /// zero.
+    pub fn spawn_position(&self) -> (BlockPos, f32) {
+        self.spawn_position
+    }
+
     /// Gets the last death location of this client. The client will see
     /// `minecraft:recovery_compass` items point at the returned position.
     ///
@@ -519,12 +929,26 @@ impl<C: Config> Client<C> {
         self.death_location = location;
     }
 
+    /// Clears the last death location so that `minecraft:recovery_compass`
+    /// items stop pointing at it. Equivalent to `set_death_location(None)`.
+    ///
+    /// The protocol has no way to update the death location of a spawned
+    /// client, so the change is only seen by the client when it is next
+    /// (re)spawned. To make the compass spin immediately, respawn the client
+    /// in its current world with [`Self::respawn`] after calling this.
+    pub fn clear_death_location(&mut self) {
+        self.death_location = None;
+    }
+
     /// Gets the client's game mode.
     pub fn game_mode(&self) -> GameMode {
         self.game_mode
     }
 
     /// Sets the client's game mode.
+    ///
+    /// The client's abilities are updated to match the game mode. For
+    /// instance, clients in spectator mode are made to fly.
     pub fn set_game_mode(&mut self, game_mode: GameMode) {
         if self.game_mode != game_mode {
             self.game_mode = game_mode;
@@ -534,10 +958,28 @@ impl<C: Config> Client<C> {
                     reason: GameStateChangeReason::ChangeGameMode,
                     value: game_mode as i32 as f32,
                 });
+
+                self.queue_packet(&self.abilities_packet());
             }
         }
     }
 
+    /// Returns the abilities packet for the client's current game mode.
+    fn abilities_packet(&self) -> PlayerAbilitiesS2c {
+        let creative = self.game_mode == GameMode::Creative;
+        let spectator = self.game_mode == GameMode::Spectator;
+
+        PlayerAbilitiesS2c {
+            flags: PlayerAbilitiesFlags::new()
+                .with_invulnerable(creative || spectator)
+                .with_flying(spectator)
+                .with_allow_flying(creative || spectator)
+                .with_instant_break(creative),
+            flying_speed: 0.05,
+            fov_modifier: 0.1,
+        }
+    }
+
     /// Sets whether or not the client sees rain.
     pub fn set_raining(&mut self, raining: bool) {
         self.queue_packet(&GameEvent {
@@ -595,12 +1037,101 @@ impl<C: Config> Client<C> {
         });
     }
 
+    /// Plays a sound to the client at a given position using the sound's
+    /// numeric ID in the sound event registry.
+    ///
+    /// Unlike [`play_sound`](Self::play_sound), the sound name is not sent
+    /// over the network. The ID must refer to a sound known to the client.
+    pub fn play_sound_id(
+        &mut self,
+        id: i32,
+        category: SoundCategory,
+        pos: Vec3<f64>,
+        volume: f32,
+        pitch: f32,
+    ) {
+        self.queue_packet(&SoundEffect {
+            id: VarInt(id),
+            category,
+            position: (pos.as_() * 8).into_array(),
+            volume,
+            pitch,
+            seed: rand::random(),
+        });
+    }
+
+    /// Stops sounds that are playing for the client.
+    ///
+    /// If `sound` is `Some`, only sounds with that name are stopped. If
+    /// `category` is `Some`, only sounds in that category are stopped. If both
+    /// are `None`, every sound is stopped.
+    pub fn stop_sound(&mut self, sound: Option<Ident<&str>>, category: Option<SoundCategory>) {
+        self.queue_packet(&StopSound {
+            filter: StopSoundFilter {
+                source: category,
+                sound,
+            },
+        });
+    }
+
+    /// Spawns particles at the given position for this client.
+    ///
+    /// `count` particles are spawned with random positions offset by up to
+    /// `offset` on each axis and speeds of up to `max_speed`. If `count` is
+    /// zero, a single particle is spawned with `offset` used as its velocity
+    /// instead. If `long_distance` is true, the client displays the particles
+    /// from up to 256 blocks away instead of 32.
+    pub fn spawn_particle(
+        &mut self,
+        particle: Particle,
+        long_distance: bool,
+        pos: Vec3<f64>,
+        offset: Vec3<f32>,
+        max_speed: f32,
+        count: i32,
+    ) {
+        let mut data = mem::take(&mut self.scratch);
+        data.clear();
+
+        match particle.encode_data(&mut data) {
+            Ok(()) => self.queue_packet(&ParticleS2c {
+                particle_id: VarInt(particle.id()),
+                long_distance,
+                position: pos.into_array(),
+                offset: offset.into_array(),
+                max_speed,
+                count,
+                data: RawBytes(&data),
+            }),
+            Err(e) => warn!(
+                username = %self.username,
+                uuid = %self.uuid,
+                ip = %self.ip,
+                "failed to encode particle data: {e:#}"
+            ),
+        }
+
+        self.scratch = data;
+    }
+
+    /// Plays a note block sound at the given note to this client. Notes range
+    /// from `0` to `24` and are converted to a pitch with [`note_pitch`].
+    ///
+    /// `name` is the name of the note block sound to play, such as
+    /// `minecraft:block.note_block.harp`.
+    pub fn play_note(&mut self, name: Ident<&str>, pos: Vec3<f64>, note: u8) {
+        self.play_sound(name, SoundCategory::Record, pos, 3.0, note_pitch(note));
+    }
+
     /// Sets the title this client sees.
     ///
     /// A title is a large piece of text displayed in the center of the screen
     /// which may also include a subtitle underneath it. The title can be
     /// configured to fade in and out using the [`SetTitleAnimationTimes`]
     /// struct.
+    ///
+    /// Both the title and subtitle are always sent, so passing an empty
+    /// subtitle removes any subtitle that was previously shown.
     pub fn set_title(
         &mut self,
         title: impl Into<Text>,
@@ -611,10 +1142,7 @@ impl<C: Config> Client<C> {
         let subtitle = subtitle.into();
 
         self.queue_packet(&SetTitleText(title));
-
-        if !subtitle.is_empty() {
-            self.queue_packet(&SetSubtitleText(subtitle));
-        }
+        self.queue_packet(&SetSubtitleText(subtitle));
 
         if let Some(anim) = animation.into() {
             self.queue_packet(&anim);
@@ -638,6 +1166,18 @@ impl<C: Config> Client<C> {
         });
     }
 
+    /// Puts every item of the given kind on cooldown for this client, which
+    /// shows a gray sweep over the item in the hotbar.
+    ///
+    /// The client cannot use the item until the cooldown ends. A cooldown of
+    /// `0` ticks clears any cooldown on the item.
+    pub fn set_cooldown(&mut self, item: ItemKind, cooldown_ticks: u16) {
+        self.queue_packet(&SetCooldown {
+            item_id: VarInt(item.to_raw().into()),
+            cooldown_ticks: VarInt(cooldown_ticks.into()),
+        });
+    }
+
     /// Sets the speed at which the client can run on the ground.
     pub fn set_movement_speed(&mut self, speed: f64) {
         self.queue_packet(&UpdateAttributes {
@@ -650,23 +1190,39 @@ impl<C: Config> Client<C> {
         });
     }
 
-    /// Removes the current title from the client's screen.
+    /// Removes the current title from the client's screen and resets the
+    /// title's fade times to their defaults.
+    ///
+    /// Any custom [`SetTitleAnimationTimes`] must be sent again before the
+    /// next title. To keep them, use [`Self::hide_title`] instead.
     pub fn clear_title(&mut self) {
         self.queue_packet(&ClearTitles { reset: true });
     }
 
-    /// Sets the XP bar visible above hotbar and total experience.
+    /// Removes the current title from the client's screen without resetting
+    /// the title's fade times.
+    ///
+    /// Unlike [`Self::clear_title`], the animation times set with the last
+    /// title remain in effect for the next one.
+    pub fn hide_title(&mut self) {
+        self.queue_packet(&ClearTitles { reset: false });
+    }
+
+    /// Sets the XP bar visible above hotbar and the experience level.
+    ///
+    /// The client is also sent its total experience points, which are
+    /// computed from `level` and `bar` with [`xp_at_level`] so that all three
+    /// values are consistent.
     ///
     /// # Arguments
     /// * `bar` - Floating value in the range `0.0..=1.0` indicating progress on
     ///   the XP bar.
     /// * `level` - Number above the XP bar.
-    /// * `total_xp` - TODO.
-    pub fn set_level(&mut self, bar: f32, level: i32, total_xp: i32) {
+    pub fn set_level(&mut self, bar: f32, level: i32) {
         self.queue_packet(&SetExperience {
             bar,
             level: level.into(),
-            total_xp: total_xp.into(),
+            total_xp: xp_at_level(level, bar).into(),
         })
     }
 
@@ -688,12 +1244,52 @@ impl<C: Config> Client<C> {
 
     /// Kills the client and shows `message` on the death screen. If an entity
     /// killed the player, pass its ID into the function.
+    ///
+    /// If [automatic respawning](Self::set_auto_respawn) is enabled, the
+    /// client is also respawned in its current world and moved to its current
+    /// [spawn position](Self::set_spawn_position). The position is sent to the
+    /// client after the respawn packet when it is next updated.
     pub fn kill(&mut self, killer: Option<EntityId>, message: impl Into<Text>) {
         self.queue_packet(&CombatDeath {
             player_id: VarInt(0),
             entity_id: killer.map_or(-1, |k| k.to_raw()),
             message: message.into(),
         });
+
+        if self.bits.auto_respawn() {
+            self.respawn_at_spawn_position();
+        }
+    }
+
+    /// Respawns the client in its current world and moves it to its spawn
+    /// position.
+    fn respawn_at_spawn_position(&mut self) {
+        let (pos, yaw) = self.spawn_position;
+
+        self.respawn(self.world);
+        self.position = Vec3::new(pos.x as f64 + 0.5, pos.y as f64, pos.z as f64 + 0.5);
+        self.yaw = yaw;
+        self.pitch = 0.0;
+        self.bits.set_spawn_teleport(true);
+    }
+
+    /// Sets whether the client is respawned immediately when it is killed with
+    /// [`Self::kill`], instead of waiting for it to click the respawn button
+    /// and handling [`ClientEvent::PerformRespawn`]. Disabled by default.
+    ///
+    /// Clients that die on their own, such as when their health is set to
+    /// zero with [`Self::set_health_and_food`], are respawned in the same way
+    /// once they request it. [`ClientEvent::PerformRespawn`] is still returned
+    /// for them. Disable the [respawn screen](Self::set_respawn_screen) to
+    /// have them request it without clicking the respawn button.
+    pub fn set_auto_respawn(&mut self, enabled: bool) {
+        self.bits.set_auto_respawn(enabled);
+    }
+
+    /// If the client is respawned automatically. See
+    /// [`Self::set_auto_respawn`].
+    pub fn auto_respawn(&self) -> bool {
+        self.bits.auto_respawn()
     }
 
     /// Respawns client. Optionally can roll the credits before respawning.
@@ -767,6 +1363,53 @@ impl<C: Config> Client<C> {
         }
     }
 
+    /// Triggers an entity event on the client's own player entity, which is the
+    /// entity with the same UUID as the client.
+    ///
+    /// Unlike [`Self::send_entity_event`], the event is seen by every client
+    /// viewing the player entity, such as the totem animation when a player is
+    /// saved from death. The event is also sent to this client.
+    ///
+    /// Returns `false` without sending anything if the player entity does not
+    /// exist.
+    pub fn broadcast_entity_event(
+        &mut self,
+        entities: &mut Entities<C>,
+        event: entity::EntityEvent,
+    ) -> bool {
+        match entities.get_with_uuid(self.uuid) {
+            Some(id) => {
+                entities[id].push_event(event);
+                self.send_entity_event(event);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Starts the item use animation, such as eating or drinking, for the item
+    /// in the given hand.
+    ///
+    /// This sets the `using_item` and `off_hand_active` flags of the client's
+    /// own [`Player`] data. Broadcast [`PlayerDataField::LivingFlags`] with
+    /// [`Self::set_player_field_broadcast`] for other clients to see the
+    /// animation.
+    pub fn start_using_item(&mut self, hand: Hand) {
+        self.player_data.set_using_item(true);
+        self.player_data.set_off_hand_active(hand == Hand::Off);
+    }
+
+    /// Stops the item use animation and tells the client it has finished using
+    /// the item, which plays the finishing effect of food and potions.
+    ///
+    /// The server is still responsible for the item's effects, such as
+    /// restoring food or removing the item from the client's inventory.
+    pub fn finish_using_item(&mut self) {
+        self.player_data.set_using_item(false);
+        self.player_data.set_off_hand_active(false);
+        self.send_entity_event(entity::EntityEvent::ConsumeItem);
+    }
+
     /// The current view distance of this client measured in chunks. The client
     /// will not be able to see chunks and entities past this distance.
     ///
@@ -783,6 +1426,61 @@ impl<C: Config> Client<C> {
         self.view_distance = dist.clamp(2, 32);
     }
 
+    /// The current simulation distance of this client measured in chunks. The
+    /// client only ticks entities and other game logic within this distance.
+    ///
+    /// The result is in `2..=32`. The default is 16.
+    pub fn simulation_distance(&self) -> u8 {
+        self.simulation_distance
+    }
+
+    /// Sets the simulation distance. The client will not tick entities past
+    /// this distance.
+    ///
+    /// The new simulation distance is measured in chunks and is clamped to
+    /// `2..=32`.
+    pub fn set_simulation_distance(&mut self, dist: u8) {
+        self.simulation_distance = dist.clamp(2, 32);
+    }
+
+    /// The current entity view distance of this client measured in chunks.
+    /// The client will not be able to see entities past this distance, even
+    /// if the chunks they are in are loaded.
+    ///
+    /// The result is never larger than the [view distance].
+    ///
+    /// [view distance]: Self::view_distance
+    pub fn entity_view_distance(&self) -> u8 {
+        self.entity_view_distance.min(self.view_distance)
+    }
+
+    /// Sets the entity view distance. The client will not be able to see
+    /// entities past this distance, which allows terrain to be rendered
+    /// further than entities are tracked.
+    ///
+    /// The new entity view distance is measured in chunks and is clamped to
+    /// the [view distance]. By default, it is equal to the view distance.
+    ///
+    /// [view distance]: Self::view_distance
+    pub fn set_entity_view_distance(&mut self, dist: u8) {
+        self.entity_view_distance = dist;
+    }
+
+    /// The number of chunks sent to the client during the most recent update.
+    ///
+    /// Together with [`Self::chunks_unloaded_this_tick`], this is useful for
+    /// diagnosing chunk churn, such as a client repeatedly crossing a chunk
+    /// border with a small view distance.
+    pub fn chunks_loaded_this_tick(&self) -> u32 {
+        self.chunks_loaded_this_tick
+    }
+
+    /// The number of chunks unloaded for the client during the most recent
+    /// update.
+    pub fn chunks_unloaded_this_tick(&self) -> u32 {
+        self.chunks_unloaded_this_tick
+    }
+
     /// Enables hardcore mode. This changes the design of the client's hearts.
     ///
     /// To have any visible effect, this function must be called on the same
@@ -819,6 +1517,86 @@ impl<C: Config> Client<C> {
             forced,
             prompt_message,
         });
+
+        if self.resource_pack_bar.is_none() {
+            if let Some(title) = self.resource_pack_bar_title.clone() {
+                let id = Uuid::from_u128(rand::random());
+
+                self.queue_packet(&BossBarPacket {
+                    id,
+                    action: BossBarAction::Add {
+                        title,
+                        health: 1.0,
+                        color: BossBarColor::Yellow,
+                        division: BossBarDivision::TwentyNotches,
+                        flags: BossBarFlags::new(),
+                    },
+                });
+
+                self.resource_pack_bar = Some(id);
+            }
+        }
+    }
+
+    /// Sets the title of the boss bar shown while the client downloads a
+    /// resource pack sent with [`Self::set_resource_pack`]. The boss bar is
+    /// removed once the client reports that the pack was loaded, declined, or
+    /// failed to download. If `None`, no boss bar is shown.
+    ///
+    /// Clients do not report their download progress, so the boss bar is
+    /// shown full until the download finishes.
+    pub fn set_resource_pack_progress_bar(&mut self, title: impl Into<Option<Text>>) {
+        self.resource_pack_bar_title = title.into();
+    }
+
+    /// Shows a boss bar to this client. If a boss bar with the same `id` is
+    /// already shown, it is replaced.
+    pub fn add_boss_bar(&mut self, id: Uuid, bar: BossBar) {
+        self.boss_bars.insert(id);
+
+        self.queue_packet(&BossBarPacket {
+            id,
+            action: bar.into_add_action(),
+        });
+    }
+
+    /// Sets how full a boss bar shown with [`Self::add_boss_bar`] is. `health`
+    /// is clamped to `0.0..=1.0`.
+    ///
+    /// Has no effect if the boss bar is not shown.
+    pub fn set_boss_bar_health(&mut self, id: Uuid, health: f32) {
+        if self.boss_bars.contains(&id) {
+            self.queue_packet(&BossBarPacket {
+                id,
+                action: BossBarAction::UpdateHealth(health.clamp(0.0, 1.0)),
+            });
+        }
+    }
+
+    /// Sets the title of a boss bar shown with [`Self::add_boss_bar`].
+    ///
+    /// Has no effect if the boss bar is not shown.
+    pub fn set_boss_bar_title(&mut self, id: Uuid, title: impl Into<Text>) {
+        if self.boss_bars.contains(&id) {
+            self.queue_packet(&BossBarPacket {
+                id,
+                action: BossBarAction::UpdateTitle(title.into()),
+            });
+        }
+    }
+
+    /// Removes a boss bar shown with [`Self::add_boss_bar`]. Returns whether
+    /// the boss bar was shown.
+    pub fn remove_boss_bar(&mut self, id: Uuid) -> bool {
+        if self.boss_bars.remove(&id) {
+            self.queue_packet(&BossBarPacket {
+                id,
+                action: BossBarAction::Remove,
+            });
+            true
+        } else {
+            false
+        }
     }
 
     /// Sets the world_age and the current in-game time.
@@ -849,6 +1627,50 @@ impl<C: Config> Client<C> {
         self.send = None;
     }
 
+    /// Starts recording every packet sent to and received from this client to
+    /// the file at `path`, replacing the file if it exists. See
+    /// [`packet_capture`](crate::packet_capture) for the format.
+    ///
+    /// Any capture already in progress is stopped first.
+    #[cfg(feature = "packet_capture")]
+    pub fn start_capture(&mut self, path: impl AsRef<std::path::Path>) -> std::io::Result<()> {
+        self.stop_capture()?;
+
+        let capture = crate::packet_capture::PacketCapture::create(path)?;
+
+        if let Some(send) = &mut self.send {
+            send.set_capture(Some(capture.clone()));
+        }
+        self.recv.set_capture(Some(capture));
+
+        Ok(())
+    }
+
+    /// Stops recording packets started with [`Self::start_capture`] and
+    /// flushes the capture file. Has no effect if no capture is in progress.
+    #[cfg(feature = "packet_capture")]
+    pub fn stop_capture(&mut self) -> std::io::Result<()> {
+        if let Some(send) = &mut self.send {
+            send.set_capture(None);
+        }
+
+        match self.recv.take_capture() {
+            Some(capture) => capture.flush(),
+            None => Ok(()),
+        }
+    }
+
+    /// Returns `true` if the client is disconnected and this is the first time
+    /// this function observed the disconnection.
+    pub(crate) fn take_leave_notification(&mut self) -> bool {
+        if self.is_disconnected() && !self.bits.leave_notified() {
+            self.bits.set_leave_notified(true);
+            true
+        } else {
+            false
+        }
+    }
+
     /// Returns an immutable reference to the client's own [`Player`] data.
     pub fn player(&self) -> &Player {
         &self.player_data
@@ -856,18 +1678,78 @@ impl<C: Config> Client<C> {
 
     /// Returns a mutable reference to the client's own [`Player`] data.
     ///
-    /// Changes made to this data is only visible to this client.
+    /// Changes made to this data is only visible to this client, unless the
+    /// modified field is marked for broadcast with
+    /// [`Self::set_player_field_broadcast`].
     pub fn player_mut(&mut self) -> &mut Player {
         &mut self.player_data
     }
 
-    pub fn slot(&self, idx: u16) -> Option<&ItemStack> {
+    /// Returns whether the given field of the client's own [`Player`] data is
+    /// copied to the player entity seen by others.
+    pub fn player_field_broadcast(&self, field: PlayerDataField) -> bool {
+        (self.broadcast_player_fields >> field as u8) & 1 == 1
+    }
+
+    /// Sets whether the given field of the client's own [`Player`] data is
+    /// copied to the player entity seen by others.
+    ///
+    /// The player entity is the [`Entity`](crate::entity::Entity) with the same
+    /// UUID as this client. After [`Config::update`] is called each tick, the
+    /// value of each broadcast field is copied to the entity's data,
+    /// overwriting any value set on the entity directly. This is useful for
+    /// data such as the custom name, glowing flag or score which are otherwise
+    /// only visible to this client.
+    ///
+    /// No fields are broadcast by default.
+    pub fn set_player_field_broadcast(&mut self, field: PlayerDataField, broadcast: bool) {
+        if broadcast {
+            self.broadcast_player_fields |= 1 << field as u8;
+        } else {
+            self.broadcast_player_fields &= !(1 << field as u8);
+        }
+    }
+
+    /// Copies the broadcast fields of the client's own player data to the
+    /// player entity with the same UUID.
+    pub(crate) fn update_broadcast_player_data(&self, entities: &mut Entities<C>) {
+        if self.broadcast_player_fields == 0 {
+            return;
+        }
+
+        if let Some(id) = entities.get_with_uuid(self.uuid) {
+            if let TrackedData::Player(player) = entities[id].data_mut() {
+                player.copy_fields(&self.player_data, self.broadcast_player_fields);
+            }
+        }
+    }
+
+    /// Gets the item in the slot of the client's own inventory with the given
+    /// raw index. See [`PlayerInventorySlot`] for the layout.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the index is out of range.
+    pub fn slot(&self, idx: u16) -> Option<&ItemStack> {
         self.slots
             .get(idx as usize)
             .expect("slot index out of range")
             .as_ref()
     }
 
+    /// Like [`Self::slot`], but takes a typed [`PlayerInventorySlot`]. Returns
+    /// `None` if the slot is out of range.
+    pub fn inventory_slot(&self, slot: PlayerInventorySlot) -> Option<&ItemStack> {
+        self.slot(slot.to_raw()?)
+    }
+
+    /// Replaces the item in the slot of the client's own inventory with the
+    /// given raw index. The previous item in the slot is returned. See
+    /// [`PlayerInventorySlot`] for the layout.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the index is out of range.
     pub fn replace_slot(
         &mut self,
         idx: u16,
@@ -880,11 +1762,170 @@ impl<C: Config> Client<C> {
 
         if new != *old {
             self.modified_slots |= 1 << idx;
+
+            if idx == self.held_item_raw_slot() {
+                self.bits.set_held_item_modified(true);
+            }
         }
 
         mem::replace(old, new)
     }
 
+    /// Like [`Self::replace_slot`], but takes a typed [`PlayerInventorySlot`].
+    ///
+    /// # Panics
+    ///
+    /// Panics if the slot is out of range.
+    pub fn replace_inventory_slot(
+        &mut self,
+        slot: PlayerInventorySlot,
+        item: impl Into<Option<ItemStack>>,
+    ) -> Option<ItemStack> {
+        let idx = slot.to_raw().expect("slot index out of range");
+        self.replace_slot(idx, item)
+    }
+
+    /// Sets the item in the client's helmet slot. The previous item in the
+    /// slot is returned.
+    ///
+    /// This only changes the client's own inventory. For other clients to see
+    /// the armor, the equipment of the client's player entity must be updated
+    /// as well.
+    pub fn set_helmet(&mut self, item: impl Into<Option<ItemStack>>) -> Option<ItemStack> {
+        self.replace_inventory_slot(PlayerInventorySlot::Helmet, item)
+    }
+
+    /// Sets the item in the client's chestplate slot. The previous item in the
+    /// slot is returned.
+    ///
+    /// See [`Self::set_helmet`] for more information.
+    pub fn set_chestplate(&mut self, item: impl Into<Option<ItemStack>>) -> Option<ItemStack> {
+        self.replace_inventory_slot(PlayerInventorySlot::Chestplate, item)
+    }
+
+    /// Sets the item in the client's leggings slot. The previous item in the
+    /// slot is returned.
+    ///
+    /// See [`Self::set_helmet`] for more information.
+    pub fn set_leggings(&mut self, item: impl Into<Option<ItemStack>>) -> Option<ItemStack> {
+        self.replace_inventory_slot(PlayerInventorySlot::Leggings, item)
+    }
+
+    /// Sets the item in the client's boots slot. The previous item in the slot
+    /// is returned.
+    ///
+    /// See [`Self::set_helmet`] for more information.
+    pub fn set_boots(&mut self, item: impl Into<Option<ItemStack>>) -> Option<ItemStack> {
+        self.replace_inventory_slot(PlayerInventorySlot::Boots, item)
+    }
+
+    /// Sets the item in the client's offhand slot. The previous item in the
+    /// slot is returned.
+    ///
+    /// See [`Self::set_helmet`] for more information.
+    pub fn set_offhand(&mut self, item: impl Into<Option<ItemStack>>) -> Option<ItemStack> {
+        self.replace_inventory_slot(PlayerInventorySlot::Offhand, item)
+    }
+
+    /// Swaps the item in the hotbar slot the client has selected with the item
+    /// in its offhand slot.
+    pub fn swap_hands(&mut self) {
+        let main = self.held_item_raw_slot();
+        let offhand = PlayerInventorySlot::Offhand.to_raw().unwrap();
+
+        let item = self.slot(main).cloned();
+        let item = self.replace_slot(offhand, item);
+        self.replace_slot(main, item);
+    }
+
+    /// Sets whether the client's hands are swapped with
+    /// [`swap_hands`](Self::swap_hands) when it presses the swap hands key.
+    /// The [`ClientEvent::SwapItemInHand`] event is generated either way.
+    ///
+    /// The client does not swap its hands on its own, so this should be
+    /// enabled unless the server handles the event itself. Disabled by
+    /// default.
+    pub fn set_auto_swap_hands(&mut self, enabled: bool) {
+        self.bits.set_auto_swap_hands(enabled);
+    }
+
+    /// If the client's hands are swapped automatically. See
+    /// [`Self::set_auto_swap_hands`].
+    pub fn auto_swap_hands(&self) -> bool {
+        self.bits.auto_swap_hands()
+    }
+
+    /// Removes a single item from the hotbar slot the client has selected, or
+    /// the whole stack if `full_stack` is true. The removed items are
+    /// returned, or `None` if the slot was empty.
+    pub fn drop_held_item(&mut self, full_stack: bool) -> Option<ItemStack> {
+        let idx = self.held_item_raw_slot();
+        let mut dropped = self.replace_slot(idx, None)?;
+
+        if !full_stack && dropped.count() > 1 {
+            let mut rest = dropped.clone();
+            rest.set_count(dropped.count() - 1);
+            self.replace_slot(idx, rest);
+            dropped.set_count(1);
+        }
+
+        Some(dropped)
+    }
+
+    /// Sets whether items are removed from the client's inventory with
+    /// [`drop_held_item`](Self::drop_held_item) when it presses the drop key.
+    /// The [`ClientEvent::DropItem`] event is generated either way.
+    ///
+    /// The client removes the items from its own inventory regardless, so
+    /// servers which cancel drops should resend the slot. Disabled by default.
+    pub fn set_auto_drop_items(&mut self, enabled: bool) {
+        self.bits.set_auto_drop_items(enabled);
+    }
+
+    /// If dropped items are removed from the client's inventory
+    /// automatically. See [`Self::set_auto_drop_items`].
+    pub fn auto_drop_items(&self) -> bool {
+        self.bits.auto_drop_items()
+    }
+
+    /// Gets the index of the hotbar slot the client has selected. The value
+    /// is in `0..9`.
+    ///
+    /// The selection is updated when the [`ClientEvent::SetHeldItem`] event
+    /// for it is returned by [`Self::next_event`].
+    pub fn selected_slot(&self) -> u8 {
+        self.selected_slot
+    }
+
+    /// Gets the item in the hotbar slot the client has selected.
+    ///
+    /// Whenever this item changes, the main hand equipment of the client's
+    /// player entity is updated to match so that other clients can see it.
+    pub fn held_item(&self) -> Option<&ItemStack> {
+        self.slot(self.held_item_raw_slot())
+    }
+
+    fn held_item_raw_slot(&self) -> u16 {
+        PlayerInventorySlot::Hotbar(self.selected_slot)
+            .to_raw()
+            .expect("held item slot should be in the hotbar")
+    }
+
+    /// Copies the held item to the main hand of the client's player entity if
+    /// it changed this tick.
+    pub(crate) fn update_held_item_equipment(&mut self, entities: &mut Entities<C>) {
+        if !self.bits.held_item_modified() {
+            return;
+        }
+
+        // Keep the change pending until the player entity exists.
+        if let Some(id) = entities.get_with_uuid(self.uuid) {
+            let item = self.held_item().cloned();
+            entities[id].set_equipment(EquipmentSlot::MainHand, item);
+            self.bits.set_held_item_modified(false);
+        }
+    }
+
     pub fn cursor_item(&self) -> Option<&ItemStack> {
         self.cursor_item.as_ref()
     }
@@ -892,7 +1933,7 @@ impl<C: Config> Client<C> {
     pub fn replace_cursor_item(&mut self, item: impl Into<Option<ItemStack>>) -> Option<ItemStack> {
         let new = item.into();
         if self.cursor_item != new {
-            todo!("set cursor item bit");
+            self.bits.set_cursor_item_modified(true);
         }
 
         mem::replace(&mut self.cursor_item, new)
@@ -909,9 +1950,55 @@ impl<C: Config> Client<C> {
         }
     }
 
+    /// Closes the window the client has open, if any.
+    ///
+    /// The open inventory is reset to [`InventoryId::NULL`]. Unlike calling
+    /// [`set_open_inventory`](Self::set_open_inventory) with
+    /// [`InventoryId::NULL`], this tells the client to close its screen, so
+    /// its cursor item and slots stay in sync with the server. No packet is
+    /// sent if no window is open.
+    pub fn close_screen(&mut self) {
+        if self.bits.window_open() {
+            self.queue_packet(&CloseContainerS2c {
+                window_id: self.window_id,
+            });
+            self.bits.set_window_open(false);
+        }
+
+        self.open_inventory = InventoryId::NULL;
+        self.bits.set_open_inventory_modified(false);
+    }
+
+    /// Returns the ID of the window most recently opened for this client with
+    /// [`Self::set_open_inventory`], or `0` if no window was opened yet.
+    ///
+    /// A new ID in `1..=100` is chosen each time a window is opened. It is the
+    /// `window_id` reported by [`ClientEvent::ClickContainer`] and
+    /// [`ClientEvent::CloseContainer`] for that window, which can be used to
+    /// ignore events meant for a window that was already replaced.
+    pub fn window_id(&self) -> u8 {
+        self.window_id
+    }
+
     pub fn next_event(&mut self) -> Option<ClientEvent> {
         match next_event_fallible(self) {
-            Ok(event) => event,
+            Ok(event) => {
+                if let Some(
+                    ClientEvent::ResourcePackLoaded
+                    | ClientEvent::ResourcePackDeclined
+                    | ClientEvent::ResourcePackFailedDownload,
+                ) = event
+                {
+                    if let Some(id) = self.resource_pack_bar.take() {
+                        self.queue_packet(&BossBarPacket {
+                            id,
+                            action: BossBarAction::Remove,
+                        });
+                    }
+                }
+
+                event
+            }
             Err(e) => {
                 warn!(
                     username = %self.username,
@@ -931,6 +2018,45 @@ impl<C: Config> Client<C> {
         }
     }
 
+    /// Requests the init packets of the partition cells whose entities this
+    /// client will load during its next update. This follows the view changes
+    /// made in [`Self::update`], except for clients moved out of a deleted
+    /// world, which load their entities without the cache.
+    pub(crate) fn request_entity_init_packets(&self, worlds: &Worlds<C>) {
+        let limbo = self.bits.limbo();
+
+        if self.send.is_none() || limbo {
+            return;
+        }
+
+        let Some(world) = worlds.get(self.world) else {
+            return;
+        };
+
+        let old_chunk_pos = ChunkPos::at(self.old_position.x, self.old_position.z);
+        let chunk_pos = ChunkPos::at(self.position.x, self.position.z);
+
+        let entity_view_distance = self.entity_view_distance();
+        let old_entity_view_distance = self.old_entity_view_distance;
+
+        let reload_all = self.old_world != self.world || self.bits.old_limbo() != limbo;
+
+        if !reload_all
+            && old_chunk_pos == chunk_pos
+            && old_entity_view_distance == entity_view_distance
+        {
+            return;
+        }
+
+        for pos in chunk_pos.in_view(entity_view_distance) {
+            if reload_all || !pos.is_in_view(old_chunk_pos, old_entity_view_distance) {
+                if let Some((_, cell)) = world.chunks.chunk_and_cell(pos) {
+                    cell.request_init_packets();
+                }
+            }
+        }
+    }
+
     pub(crate) fn update(
         &mut self,
         current_tick: Ticks,
@@ -952,7 +2078,15 @@ impl<C: Config> Client<C> {
             ) {
                 Ok(()) => self.send = Some(send),
                 Err(e) => {
-                    let _ = send.append_packet(&DisconnectPlay { reason: "".into() });
+                    // Only reasons meant for the player are shown. Everything else could leak
+                    // server internals.
+                    let reason = e
+                        .downcast_ref::<DisconnectReason>()
+                        .map_or(INTERNAL_ERROR_REASON, |r| r.0);
+
+                    let _ = send.append_packet(&DisconnectPlay {
+                        reason: reason.into(),
+                    });
                     warn!(
                         username = %self.username,
                         uuid = %self.uuid,
@@ -967,8 +2101,9 @@ impl<C: Config> Client<C> {
     }
 
     /// Called by [`Self::update`] with the possibility of exiting early with an
-    /// error. If an error does occur, the client is abruptly disconnected and
-    /// the error is logged.
+    /// error. If an error does occur, the client is disconnected and the error
+    /// is logged. The player is shown the message of a [`DisconnectReason`]
+    /// error or a generic message otherwise.
     #[allow(clippy::too_many_arguments)]
     fn update_fallible(
         &mut self,
@@ -982,6 +2117,40 @@ impl<C: Config> Client<C> {
     ) -> anyhow::Result<()> {
         debug_assert!(self.entities_to_unload.is_empty());
 
+        self.chunks_loaded_this_tick = 0;
+        self.chunks_unloaded_this_tick = 0;
+
+        // Move the client out of a world that was deleted with a fallback.
+        let mut fallback_pos = match worlds.get(self.world).and_then(|w| w.fallback()) {
+            Some((fallback_world, fallback_pos)) => {
+                self.respawn(fallback_world);
+                Some(fallback_pos)
+            }
+            None => None,
+        };
+
+        // Refuse to respawn the client into a world it can't be sent to.
+        if self.bits.respawn() && !self.created_this_tick() {
+            let valid = worlds.get(self.world).map_or(false, |w| {
+                !w.deleted() && shared.dimensions().any(|(id, _)| id == w.dimension())
+            });
+
+            if !valid {
+                warn!(
+                    username = %self.username,
+                    uuid = %self.uuid,
+                    ip = %self.ip,
+                    "refusing to respawn client in invalid world {:?}",
+                    self.world
+                );
+
+                self.world = self.old_world;
+                self.bits.set_respawn(false);
+                // The client stays where it was, so don't teleport it.
+                fallback_pos = None;
+            }
+        }
+
         let Some(world) = worlds.get(self.world) else {
             bail!("client is in an invalid world")
         };
@@ -1008,13 +2177,16 @@ impl<C: Config> Client<C> {
                 game_mode: self.game_mode,
                 previous_game_mode: -1,
                 dimension_names,
-                registry_codec: shared.registry_codec().clone(),
+                registry_codec: self
+                    .registry_codec
+                    .take()
+                    .unwrap_or_else(|| shared.registry_codec().clone()),
                 dimension_type_name: world.dimension().dimension_type_name(),
                 dimension_name: world.dimension().dimension_name(),
-                hashed_seed: 10,
+                hashed_seed: world.hashed_seed(),
                 max_players: VarInt(0), // Unused
                 view_distance: VarInt(self.view_distance() as i32),
-                simulation_distance: VarInt(16),
+                simulation_distance: VarInt(self.simulation_distance.into()),
                 reduced_debug_info: false,
                 enable_respawn_screen: self.bits.respawn_screen(),
                 is_debug: false,
@@ -1024,6 +2196,8 @@ impl<C: Config> Client<C> {
                     .map(|(id, pos)| (id.dimension_name(), pos)),
             })?;
 
+            send.append_packet(&self.abilities_packet())?;
+
             if let Some(id) = &self.player_list {
                 player_lists[id].write_init_packets(&mut *send)?;
             }
@@ -1033,13 +2207,19 @@ impl<C: Config> Client<C> {
                 send.append_packet(&SetRenderDistance(VarInt(self.view_distance.into())))?;
             }
 
+            if self.simulation_distance != self.old_simulation_distance {
+                send.append_packet(&SetSimulationDistance(VarInt(
+                    self.simulation_distance.into(),
+                )))?;
+            }
+
             if self.bits.respawn() {
                 self.bits.set_respawn(false);
 
                 send.append_packet(&RespawnOwned {
                     dimension_type_name: world.dimension().dimension_type_name(),
                     dimension_name: world.dimension().dimension_name(),
-                    hashed_seed: 0,
+                    hashed_seed: world.hashed_seed() as u64,
                     game_mode: self.game_mode(),
                     previous_game_mode: -1,
                     is_debug: false,
@@ -1070,38 +2250,40 @@ impl<C: Config> Client<C> {
             }
         }
 
+        // The position must be set after the respawn packet.
+        if let Some(pos) = fallback_pos {
+            self.position = pos;
+            send.append_packet(&self.next_teleport_packet())?;
+        } else if self.bits.spawn_teleport() {
+            send.append_packet(&self.next_teleport_packet())?;
+        }
+
+        self.bits.set_spawn_teleport(false);
+
         // Check if it's time to send another keepalive.
-        if current_tick % (shared.tick_rate() * 10) == 0 {
+        if current_tick % shared.keepalive_interval() == 0 {
             if self.bits.got_keepalive() {
                 let id = rand::random();
                 send.append_packet(&KeepAliveS2c { id })?;
                 self.last_keepalive_id = id;
+                self.missed_keepalives = 0;
                 self.bits.set_got_keepalive(false);
             } else {
-                bail!("timed out (no keepalive response)");
+                // Keep waiting for a response to the same keepalive until the
+                // client has missed too many intervals.
+                self.missed_keepalives += 1;
+
+                if self.missed_keepalives > shared.max_missed_keepalives() {
+                    bail!(DisconnectReason("Timed out (no keepalive response)"));
+                }
             }
         }
 
-        let self_entity_pos;
-        let self_entity_world;
-        let self_entity_range;
-
         // Get the entity with the same UUID as the client (if it exists).
-        if let Some(entity) = entities.get(self.self_entity) {
-            self_entity_pos = ChunkPos::at(entity.position().x, entity.position().z);
-            self_entity_world = entity.world();
-            self_entity_range = entity.self_update_range.clone();
-        } else if let Some(id) = entities.get_with_uuid(self.uuid) {
-            self.self_entity = id;
-            let entity = &entities[id];
-            self_entity_pos = ChunkPos::at(entity.position().x, entity.position().z);
-            self_entity_world = entity.world();
-            self_entity_range = entity.self_update_range.clone();
-        } else {
-            // There is no entity with the same UUID as the client.
-            self_entity_pos = ChunkPos::new(0, 0);
-            self_entity_world = WorldId::NULL;
-            self_entity_range = 0..0;
+        if entities.get(self.self_entity).is_none() {
+            if let Some(id) = entities.get_with_uuid(self.uuid) {
+                self.self_entity = id;
+            }
         }
 
         let old_chunk_pos = ChunkPos::at(self.old_position.x, self.old_position.z);
@@ -1116,8 +2298,23 @@ impl<C: Config> Client<C> {
             })?;
         }
 
+        // The position entity update intervals were measured from on the previous
+        // tick.
+        let entity_update_center = self.entity_update_center;
+
+        let entity_view_distance = self.entity_view_distance();
+        let old_entity_view_distance = self.old_entity_view_distance;
+
+        #[cfg(debug_assertions)]
+        let chunk_load_assertions = shared.chunk_load_assertions();
+
+        // Nothing in the world is visible to a client in limbo.
+        let limbo = self.bits.limbo();
+        let old_limbo = self.bits.old_limbo();
+        let old_visible_world = worlds.get(self.old_world).filter(|_| !old_limbo);
+
         // Iterate over all visible chunks from the previous tick.
-        if let Some(old_world) = worlds.get(self.old_world) {
+        if let Some(old_world) = old_visible_world {
             old_chunk_pos.try_for_each_in_view(self.old_view_distance, |pos| {
                 if let Some((chunk, cell)) = old_world.chunks.chunk_and_cell(pos) {
                     if let Some(chunk) = chunk {
@@ -1136,6 +2333,8 @@ impl<C: Config> Client<C> {
                                     &old_world.chunks,
                                 )?;
 
+                                self.chunks_loaded_this_tick += 1;
+
                                 // Don't assert that the chunk is already loaded in this case.
                                 // Chunks are allowed to be overwritten and their "created this
                                 // tick" flag will become true again.
@@ -1149,8 +2348,10 @@ impl<C: Config> Client<C> {
                                     chunk_z: pos.z,
                                 })?;
 
+                                self.chunks_unloaded_this_tick += 1;
+
                                 #[cfg(debug_assertions)]
-                                assert!(self.loaded_chunks.remove(&pos));
+                                assert!(self.loaded_chunks.remove(&pos) || !chunk_load_assertions);
                             }
                             (true, true) => {
                                 // Chunk was created and deleted this tick, so
@@ -1159,10 +2360,15 @@ impl<C: Config> Client<C> {
                         }
                     }
 
+                    // Entities are only visible within the entity view distance.
+                    if !old_chunk_pos.is_in_view(pos, old_entity_view_distance) {
+                        return Ok(());
+                    }
+
                     // Send entity spawn packets for entities entering the client's view.
                     for &(id, src_pos) in cell.incoming() {
                         if src_pos.map_or(true, |p| {
-                            !old_chunk_pos.is_in_view(p, self.old_view_distance)
+                            !old_chunk_pos.is_in_view(p, old_entity_view_distance)
                         }) {
                             // The incoming entity originated from outside the view distance, so it
                             // must be spawned.
@@ -1182,11 +2388,24 @@ impl<C: Config> Client<C> {
                         }
                     }
 
+                    // Mount passengers once every incoming entity is spawned.
+                    for &(id, src_pos) in cell.incoming() {
+                        if src_pos.map_or(true, |p| {
+                            !old_chunk_pos.is_in_view(p, old_entity_view_distance)
+                        }) {
+                            let entity = &entities[id];
+
+                            if entity.uuid() != self.uuid && !entity.passengers().is_empty() {
+                                entity.write_passengers_packet(&mut *send, id)?;
+                            }
+                        }
+                    }
+
                     // Send entity despawn packets for entities exiting the client's view.
                     for &(id, dest_pos) in cell.outgoing() {
                         if id != self.self_entity
                             && dest_pos.map_or(true, |p| {
-                                !old_chunk_pos.is_in_view(p, self.old_view_distance)
+                                !old_chunk_pos.is_in_view(p, old_entity_view_distance)
                             })
                         {
                             // The outgoing entity moved outside the view distance, so it must be
@@ -1195,15 +2414,17 @@ impl<C: Config> Client<C> {
                         }
                     }
 
-                    // Update all the entities in the chunk.
-                    if pos == self_entity_pos && self.old_world == self_entity_world {
-                        // Don't update the entity with the same UUID as the client.
-                        let bytes = cell.cached_update_packets();
-                        send.append_bytes(&bytes[..self_entity_range.start]);
-                        send.append_bytes(&bytes[self_entity_range.end..]);
-                    } else {
-                        send.append_bytes(cell.cached_update_packets());
-                    }
+                    // Update all the entities in the chunk except the entity with the same
+                    // UUID as the client. The movement of distant entities is sent less often.
+                    cell.write_update_packets(
+                        &mut *send,
+                        entities,
+                        self.self_entity,
+                        pos,
+                        current_tick,
+                        shared.entity_update_interval(old_chunk_pos.distance(pos)),
+                        |p| shared.entity_update_interval(entity_update_center.distance(p)),
+                    )?;
                 }
 
                 Ok(())
@@ -1215,13 +2436,15 @@ impl<C: Config> Client<C> {
                 })?;
                 self.entities_to_unload.clear();
             }
+
+            self.entity_update_center = old_chunk_pos;
         }
 
-        if self.old_world != self.world {
-            // Client changed the world they're in.
+        if self.old_world != self.world || old_limbo != limbo {
+            // Client changed the world they're in or entered or left limbo.
 
             // Unload all chunks and entities in old view.
-            if let Some(old_world) = worlds.get(self.old_world) {
+            if let Some(old_world) = old_visible_world {
                 // TODO: only send unload packets when old dimension == new dimension, since the
                 //       client will do the unloading for us in that case?
 
@@ -1235,16 +2458,20 @@ impl<C: Config> Client<C> {
                                     chunk_z: pos.z,
                                 })?;
 
+                                self.chunks_unloaded_this_tick += 1;
+
                                 #[cfg(debug_assertions)]
-                                assert!(self.loaded_chunks.remove(&pos));
+                                assert!(self.loaded_chunks.remove(&pos) || !chunk_load_assertions);
                             }
                         }
 
-                        self.entities_to_unload.extend(
-                            cell.entities()
-                                .filter(|&id| id != self.self_entity)
-                                .map(|id| VarInt(id.to_raw())),
-                        );
+                        if old_chunk_pos.is_in_view(pos, old_entity_view_distance) {
+                            self.entities_to_unload.extend(
+                                cell.entities()
+                                    .filter(|&id| id != self.self_entity)
+                                    .map(|id| VarInt(id.to_raw())),
+                            );
+                        }
                     }
 
                     Ok(())
@@ -1259,48 +2486,51 @@ impl<C: Config> Client<C> {
             }
 
             // Load all chunks and entities in new view.
-            chunk_pos.try_for_each_in_view(self.view_distance, |pos| {
-                if let Some((chunk, cell)) = world.chunks.chunk_and_cell(pos) {
-                    if let Some(chunk) = chunk {
-                        if !chunk.deleted() {
-                            chunk.write_chunk_data_packet(
-                                &mut *send,
-                                &mut self.scratch,
-                                pos,
-                                &world.chunks,
-                            )?;
+            if !limbo {
+                chunk_pos.try_for_each_in_view(self.view_distance, |pos| {
+                    if let Some((chunk, cell)) = world.chunks.chunk_and_cell(pos) {
+                        if let Some(chunk) = chunk {
+                            if !chunk.deleted() {
+                                chunk.write_chunk_data_packet(
+                                    &mut *send,
+                                    &mut self.scratch,
+                                    pos,
+                                    &world.chunks,
+                                )?;
 
-                            #[cfg(debug_assertions)]
-                            assert!(self.loaded_chunks.insert(pos));
-                        }
-                    }
+                                self.chunks_loaded_this_tick += 1;
 
-                    for id in cell.entities() {
-                        let entity = &entities[id];
-                        debug_assert!(!entity.deleted());
+                                #[cfg(debug_assertions)]
+                                assert!(self.loaded_chunks.insert(pos) || !chunk_load_assertions);
+                            }
+                        }
 
-                        if entity.uuid() != self.uuid {
-                            entity.send_init_packets(
-                                send,
-                                entity.position(),
-                                id,
+                        if chunk_pos.is_in_view(pos, entity_view_distance) {
+                            cell.write_init_packets(
+                                &mut *send,
+                                entities,
+                                self.self_entity,
                                 &mut self.scratch,
                             )?;
                         }
                     }
-                }
 
-                Ok(())
-            })?;
-        } else if old_chunk_pos != chunk_pos || self.old_view_distance != self.view_distance {
+                    Ok(())
+                })?;
+            }
+        } else if !limbo
+            && (old_chunk_pos != chunk_pos
+                || self.old_view_distance != self.view_distance
+                || old_entity_view_distance != entity_view_distance)
+        {
             // Client changed their view without changing the world.
             // We need to unload chunks and entities in the old view and load
             // chunks and entities in the new view. We don't need to do any
             // work where the old and new view overlap.
 
             old_chunk_pos.try_for_each_in_view(self.old_view_distance, |pos| {
-                if !pos.is_in_view(chunk_pos, self.view_distance) {
-                    if let Some((chunk, cell)) = world.chunks.chunk_and_cell(pos) {
+                if let Some((chunk, cell)) = world.chunks.chunk_and_cell(pos) {
+                    if !pos.is_in_view(chunk_pos, self.view_distance) {
                         if let Some(chunk) = chunk {
                             // Deleted chunks were already unloaded above.
                             if !chunk.deleted() {
@@ -1309,11 +2539,17 @@ impl<C: Config> Client<C> {
                                     chunk_z: pos.z,
                                 })?;
 
+                                self.chunks_unloaded_this_tick += 1;
+
                                 #[cfg(debug_assertions)]
-                                assert!(self.loaded_chunks.remove(&pos));
+                                assert!(self.loaded_chunks.remove(&pos) || !chunk_load_assertions);
                             }
                         }
+                    }
 
+                    if pos.is_in_view(old_chunk_pos, old_entity_view_distance)
+                        && !pos.is_in_view(chunk_pos, entity_view_distance)
+                    {
                         self.entities_to_unload.extend(
                             cell.entities()
                                 .filter(|&id| id != self.self_entity)
@@ -1333,8 +2569,8 @@ impl<C: Config> Client<C> {
             }
 
             chunk_pos.try_for_each_in_view(self.view_distance, |pos| {
-                if !pos.is_in_view(old_chunk_pos, self.old_view_distance) {
-                    if let Some((chunk, cell)) = world.chunks.chunk_and_cell(pos) {
+                if let Some((chunk, cell)) = world.chunks.chunk_and_cell(pos) {
+                    if !pos.is_in_view(old_chunk_pos, self.old_view_distance) {
                         if let Some(chunk) = chunk {
                             if !chunk.deleted() {
                                 chunk.write_chunk_data_packet(
@@ -1344,24 +2580,23 @@ impl<C: Config> Client<C> {
                                     &world.chunks,
                                 )?;
 
+                                self.chunks_loaded_this_tick += 1;
+
                                 #[cfg(debug_assertions)]
-                                assert!(self.loaded_chunks.insert(pos));
+                                assert!(self.loaded_chunks.insert(pos) || !chunk_load_assertions);
                             }
                         }
+                    }
 
-                        for id in cell.entities() {
-                            let entity = &entities[id];
-                            debug_assert!(!entity.deleted());
-
-                            if entity.uuid() != self.uuid {
-                                entity.send_init_packets(
-                                    send,
-                                    entity.position(),
-                                    id,
-                                    &mut self.scratch,
-                                )?;
-                            }
-                        }
+                    if pos.is_in_view(chunk_pos, entity_view_distance)
+                        && !pos.is_in_view(old_chunk_pos, old_entity_view_distance)
+                    {
+                        cell.write_init_packets(
+                            &mut *send,
+                            entities,
+                            self.self_entity,
+                            &mut self.scratch,
+                        )?;
                     }
                 }
 
@@ -1369,6 +2604,11 @@ impl<C: Config> Client<C> {
             })?;
         }
 
+        // Play the sounds in the client's world that are within hearing range.
+        if !limbo {
+            world.write_sound_packets(&mut *send, self.position)?;
+        }
+
         // Update the client's own player metadata.
         self.scratch.clear();
         self.player_data.updated_tracked_data(&mut self.scratch);
@@ -1392,12 +2632,70 @@ impl<C: Config> Client<C> {
 
         // TODO: inventory stuff below is incomplete.
 
-        // Update the client's own inventory.
+        self.update_own_inventory(send)?;
+
+        // Update the window the client has opened.
+        if self.bits.open_inventory_modified() {
+            // Open a new window.
+            self.bits.set_open_inventory_modified(false);
+
+            if let Some(inv) = inventories.get(self.open_inventory) {
+                self.window_id = self.window_id % 100 + 1;
+                self.inv_state_id += 1;
+                self.bits.set_window_open(true);
+
+                send.append_packet(&OpenScreen {
+                    window_id: VarInt(self.window_id.into()),
+                    window_type: VarInt(inv.kind() as i32),
+                    window_title: inv.title().clone(),
+                })?;
+
+                send.append_packet(&SetContainerContentEncode {
+                    window_id: self.window_id,
+                    state_id: VarInt(self.inv_state_id.0),
+                    slots: inv.slot_slice(),
+                    carried_item: &self.cursor_item,
+                })?;
+            }
+        } else {
+            // Update an already open window.
+            if let Some(inv) = inventories.get(self.open_inventory) {
+                inv.send_update(send, self.window_id, &mut self.inv_state_id)?;
+            }
+        }
+
+        self.old_world = self.world;
+        self.bits.set_old_limbo(limbo);
+        self.old_position = self.position;
+
+        if self.position_history.len() == POSITION_HISTORY_LEN {
+            self.position_history.pop_front();
+        }
+        self.position_history
+            .push_back((current_tick, self.position));
+
+        self.old_view_distance = self.view_distance;
+        self.old_simulation_distance = self.simulation_distance;
+        self.old_entity_view_distance = entity_view_distance;
+        self.player_data.clear_modifications();
+
+        if !self.bits.defer_flush() {
+            send.flush().context("failed to flush packet queue")?;
+        }
+
+        Ok(())
+    }
+
+    /// Writes the packets needed to update the client's own inventory and
+    /// cursor item.
+    ///
+    /// If every slot was modified this tick, the whole inventory is sent in a
+    /// single packet which also includes the cursor item.
+    fn update_own_inventory(&mut self, send: &mut PlayPacketSender) -> anyhow::Result<()> {
         if self.modified_slots != 0 {
-            if self.created_this_tick()
-                || self.modified_slots == u64::MAX && self.bits.cursor_item_modified()
-            {
-                // Update the whole inventory.
+            if self.created_this_tick() || self.modified_slots == ALL_SLOTS_MODIFIED {
+                // Update the whole inventory. The cursor item is included in this packet, so
+                // there is no need to send it separately below.
                 send.append_packet(&SetContainerContentEncode {
                     window_id: 0,
                     state_id: VarInt(self.inv_state_id.0),
@@ -1439,42 +2737,2164 @@ impl<C: Config> Client<C> {
             self.inv_state_id += 1;
         }
 
-        // Update the window the client has opened.
-        if self.bits.open_inventory_modified() {
-            // Open a new window.
-            self.bits.set_open_inventory_modified(false);
+        Ok(())
+    }
+}
 
-            if let Some(inv) = inventories.get(self.open_inventory) {
-                self.window_id = self.window_id % 100 + 1;
-                self.inv_state_id += 1;
+/// An error returned from [`Client::update_fallible`] which has a message
+/// that can be shown to the disconnected player.
+#[derive(Debug)]
+struct DisconnectReason(&'static str);
+
+impl fmt::Display for DisconnectReason {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.0)
+    }
+}
+
+impl std::error::Error for DisconnectReason {}
+
+/// The disconnect reason shown to players when updating the client fails for
+/// any other reason.
+const INTERNAL_ERROR_REASON: &str = "Internal server error";
+
+/// The value of `modified_slots` on a [`Client`] when every slot in its
+/// inventory has been modified.
+const ALL_SLOTS_MODIFIED: u64 = u64::MAX >> (64 - PLAYER_INVENTORY_SLOT_COUNT);
+
+/// The maximum number of positions kept by [`Client::position_history`].
+pub const POSITION_HISTORY_LEN: usize = 40;
+
+/// The number of slots in the client's own inventory.
+const PLAYER_INVENTORY_SLOT_COUNT: usize = PlayerInventorySlot::COUNT as usize;
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use tokio::sync::Semaphore;
+    use valence_protocol::packets::c2s::play::{
+        ClientCommand, CloseContainerC2s, PlayerAction, PluginMessageC2s, ResourcePackC2s,
+        SetHeldItemC2s,
+    };
+    use valence_protocol::packets::S2cPlayPacket;
+    use valence_protocol::types::DiggingStatus;
+    use valence_protocol::{BlockFace, Decode, InventoryKind, ItemKind};
+
+    use super::*;
+    use crate::chunk::entity_partition::{cache_entity_init_packets, update_entity_partition};
+    use crate::chunk::UnloadedChunk;
+    use crate::packet::PacketWriter;
+    use crate::server::{setup_server, MockConnection};
+
+    type MockConfig = crate::config::MockConfig;
+
+    /// Creates a client connected to a mock connection. Must be called from
+    /// within a tokio runtime.
+    fn new_client() -> (Client<MockConfig>, MockConnection) {
+        new_client_with_config()
+    }
+
+    /// Like [`new_client`], but for any config without custom client state.
+    fn new_client_with_config<C: Config<ClientState = ()>>() -> (Client<C>, MockConnection) {
+        let (conn, send, recv) = MockConnection::new(1 << 20);
+
+        let permit = Arc::new(Semaphore::new(1)).try_acquire_owned().unwrap();
+
+        let ncd = NewClientData {
+            username: Username::new("test".to_owned()).unwrap(),
+            uuid: Uuid::from_bytes([1; 16]),
+            ip: "127.0.0.1".parse().unwrap(),
+            textures: None,
+        };
+
+        (Client::new(send, recv, permit, ncd, None, 0, ()), conn)
+    }
+
+    /// Flushes the client's packet queue.
+    fn flush(client: &mut Client<MockConfig>) {
+        client.send.as_mut().unwrap().flush().unwrap();
+    }
+
+    #[tokio::test]
+    async fn all_slots_and_cursor_modified_sends_one_packet() {
+        let (mut client, mut conn) = new_client();
+        client.bits.set_created_this_tick(false);
+
+        for i in 0..PLAYER_INVENTORY_SLOT_COUNT as u16 {
+            client.replace_slot(i, ItemStack::new(ItemKind::Stone, 1, None));
+        }
+
+        client.cursor_item = Some(ItemStack::new(ItemKind::Dirt, 1, None));
+        client.bits.set_cursor_item_modified(true);
+
+        assert_eq!(client.modified_slots, ALL_SLOTS_MODIFIED);
+
+        let mut send = client.send.take().unwrap();
+        client.update_own_inventory(&mut send).unwrap();
+        client.send = Some(send);
+        flush(&mut client);
+
+        let mut content_count = 0;
+        let mut slot_count = 0;
+
+        conn.for_each_packet(|pkt| match pkt {
+            S2cPlayPacket::SetContainerContent(p) => {
+                assert_eq!(p.slots.len(), PLAYER_INVENTORY_SLOT_COUNT);
+                assert_eq!(
+                    p.carried_item,
+                    Some(ItemStack::new(ItemKind::Dirt, 1, None))
+                );
+                content_count += 1;
+            }
+            S2cPlayPacket::SetContainerSlot(_) => slot_count += 1,
+            _ => {}
+        });
+
+        assert_eq!(content_count, 1);
+        assert_eq!(slot_count, 0);
+        assert_eq!(client.modified_slots, 0);
+        assert!(!client.bits.cursor_item_modified());
+    }
+
+    #[tokio::test]
+    async fn offhand_is_sent_at_its_own_index() {
+        let (mut client, mut conn) = new_client();
+        client.bits.set_created_this_tick(false);
+
+        let shield = ItemStack::new(ItemKind::Shield, 1, None);
+        client.set_offhand(shield.clone());
+
+        let mut send = client.send.take().unwrap();
+        client.update_own_inventory(&mut send).unwrap();
+        client.send = Some(send);
+        flush(&mut client);
+
+        let mut slots = vec![];
+
+        conn.for_each_packet(|pkt| {
+            if let S2cPlayPacket::SetContainerSlot(p) = pkt {
+                assert_eq!(p.window_id, 0);
+                slots.push((p.slot_idx, p.slot_data));
+            }
+        });
+
+        // The vanilla client keeps the offhand after the hotbar.
+        assert_eq!(slots, [(45, Some(shield))]);
+    }
+
+    #[tokio::test]
+    async fn replaced_cursor_item_is_sent_once() {
+        let (mut client, mut conn) = new_client();
+        client.bits.set_created_this_tick(false);
+
+        let dirt = ItemStack::new(ItemKind::Dirt, 1, None);
+        assert_eq!(client.replace_cursor_item(dirt.clone()), None);
+        assert!(client.bits.cursor_item_modified());
+
+        client.replace_slot(36, ItemStack::new(ItemKind::Stone, 1, None));
+
+        // The second update has nothing left to send.
+        for _ in 0..2 {
+            let mut send = client.send.take().unwrap();
+            client.update_own_inventory(&mut send).unwrap();
+            client.send = Some(send);
+        }
+        flush(&mut client);
+
+        let mut cursor_count = 0;
+        let mut slot_count = 0;
+
+        conn.for_each_packet(|pkt| match pkt {
+            S2cPlayPacket::SetContainerSlot(p) if p.slot_idx == -1 => {
+                assert_eq!(p.window_id, -1);
+                assert_eq!(p.slot_data, Some(dirt.clone()));
+                cursor_count += 1;
+            }
+            S2cPlayPacket::SetContainerSlot(_) => slot_count += 1,
+            S2cPlayPacket::SetContainerContent(_) => panic!("inventory was resynced"),
+            _ => {}
+        });
+
+        assert_eq!(cursor_count, 1);
+        assert_eq!(slot_count, 1);
+    }
+
+    #[tokio::test]
+    async fn set_helmet_writes_helmet_slot() {
+        let (mut client, _conn) = new_client();
+
+        let helmet = ItemStack::new(ItemKind::IronHelmet, 1, None);
+        assert_eq!(client.set_helmet(helmet.clone()), None);
+
+        assert_eq!(client.slot(5), Some(&helmet));
+        assert_eq!(client.modified_slots, 1 << 5);
+    }
+
+    #[tokio::test]
+    async fn set_held_item_packets_update_selected_slot() {
+        let (mut client, mut conn) = new_client();
+        assert_eq!(client.selected_slot(), 0);
+
+        for slot in [4, 9, -1, 8] {
+            conn.send_packet(&SetHeldItemC2s { slot });
+        }
+        client.prepare_c2s_packets();
+
+        assert!(matches!(
+            client.next_event(),
+            Some(ClientEvent::SetHeldItem { slot: 4 })
+        ));
+        assert_eq!(client.selected_slot(), 4);
+        assert!(client.bits.held_item_modified());
+
+        // Out of range slots are skipped.
+        assert!(matches!(
+            client.next_event(),
+            Some(ClientEvent::SetHeldItem { slot: 8 })
+        ));
+        assert_eq!(client.selected_slot(), 8);
+
+        assert!(client.next_event().is_none());
+        assert!(!client.is_disconnected());
+    }
+
+    #[tokio::test]
+    async fn changing_held_item_queues_equipment_update() {
+        let (mut client, _conn) = new_client();
+        let mut entities = Entities::<MockConfig>::new();
+        let (id, _) = entities
+            .insert_with_uuid(entity::EntityKind::Player, client.uuid(), ())
+            .unwrap();
+
+        let sword = ItemStack::new(ItemKind::IronSword, 1, None);
+        client.replace_inventory_slot(PlayerInventorySlot::Hotbar(0), sword.clone());
+        client.update_held_item_equipment(&mut entities);
+
+        assert!(!client.bits.held_item_modified());
+        assert_eq!(
+            entities[id].equipment(EquipmentSlot::MainHand),
+            Some(&sword)
+        );
+
+        let mut bytes = vec![];
+        let mut scratch = vec![];
+        let mut compression_scratch = vec![];
+        entities[id]
+            .write_update_packets(
+                PacketWriter::new(&mut bytes, None, &mut compression_scratch),
+                id,
+                &mut scratch,
+            )
+            .unwrap();
+
+        let mut r = bytes.as_slice();
+        VarInt::decode(&mut r).unwrap();
+        match S2cPlayPacket::decode(&mut r).unwrap() {
+            S2cPlayPacket::SetEquipment(p) => {
+                assert_eq!(p.entity_id.0, id.to_raw());
+                assert_eq!(p.equipment.0, vec![(EquipmentSlot::MainHand, Some(sword))]);
+            }
+            pkt => panic!("unexpected packet {pkt:?}"),
+        }
+    }
+
+    #[tokio::test]
+    async fn spectator_game_mode_enables_flight() {
+        let (mut client, mut conn) = new_client();
+        client.bits.set_created_this_tick(false);
+
+        client.set_game_mode(GameMode::Spectator);
+        flush(&mut client);
+
+        let mut abilities = None;
+
+        conn.for_each_packet(|pkt| {
+            if let S2cPlayPacket::PlayerAbilitiesS2c(p) = pkt {
+                abilities = Some(p.flags);
+            }
+        });
+
+        let flags = abilities.expect("no abilities packet was sent");
+        assert!(flags.allow_flying());
+        assert!(flags.flying());
+        assert!(flags.invulnerable());
+        assert!(!flags.instant_break());
+    }
+
+    #[tokio::test]
+    async fn resource_pack_progress_bar_removed_on_status() {
+        let (mut client, mut conn) = new_client();
+
+        client.set_resource_pack_progress_bar(Text::from("Downloading resource pack"));
+        client.set_resource_pack("https://example.com/pack.zip", "", true, None);
+        flush(&mut client);
+
+        let mut bar_id = None;
+
+        conn.for_each_packet(|pkt| {
+            if let S2cPlayPacket::BossBar(p) = pkt {
+                assert!(matches!(p.action, BossBarAction::Add { .. }));
+                bar_id = Some(p.id);
+            }
+        });
+
+        let bar_id = bar_id.expect("progress bar was not shown");
+
+        conn.send_packet(&ResourcePackC2s::SuccessfullyLoaded);
+        client.prepare_c2s_packets();
+
+        assert!(matches!(
+            client.next_event(),
+            Some(ClientEvent::ResourcePackLoaded)
+        ));
+        flush(&mut client);
+
+        let mut removed = false;
+
+        conn.for_each_packet(|pkt| {
+            if let S2cPlayPacket::BossBar(p) = pkt {
+                assert_eq!(p.id, bar_id);
+                assert!(matches!(p.action, BossBarAction::Remove));
+                removed = true;
+            }
+        });
+
+        assert!(removed);
+    }
+
+    #[tokio::test]
+    async fn boss_bar_updates_only_sent_for_shown_bars() {
+        let (mut client, mut conn) = new_client();
+
+        let shown = Uuid::from_u128(1);
+        let unknown = Uuid::from_u128(2);
+
+        client.add_boss_bar(shown, BossBar::new("Raid"));
+        client.set_boss_bar_health(shown, 2.0);
+        client.set_boss_bar_title(shown, "Raid - Wave 2");
+        client.set_boss_bar_health(unknown, 0.5);
+        client.set_boss_bar_title(unknown, "Unknown");
+        assert!(!client.remove_boss_bar(unknown));
+        assert!(client.remove_boss_bar(shown));
+        assert!(!client.remove_boss_bar(shown));
+        flush(&mut client);
+
+        let mut actions = vec![];
+
+        conn.for_each_packet(|pkt| {
+            if let S2cPlayPacket::BossBar(p) = pkt {
+                assert_eq!(p.id, shown);
+                actions.push(p.action);
+            }
+        });
+
+        assert_eq!(
+            actions,
+            [
+                BossBar::new("Raid").into_add_action(),
+                BossBarAction::UpdateHealth(1.0),
+                BossBarAction::UpdateTitle("Raid - Wave 2".into()),
+                BossBarAction::Remove,
+            ]
+        );
+    }
+
+    #[test]
+    fn simulation_distance_sent_on_login_and_change() {
+        let shared = setup_server(MockConfig::default()).unwrap();
+        let _guard = shared.tokio_handle().enter();
+
+        let (mut client, mut conn) = new_client();
+        let entities = Entities::new();
+        let mut worlds = Worlds::new(shared.clone());
+        let player_lists = PlayerLists::new(None);
+        let inventories = Inventories::new();
+
+        let (world, _) = worlds.insert(DimensionId::default(), ());
+        client.respawn(world);
+
+        assert_eq!(client.simulation_distance(), 16);
+        client.set_simulation_distance(8);
+
+        // Returns the simulation distance sent in the login packet and the
+        // simulation distance updates sent this tick.
+        let mut tick = |client: &mut Client<MockConfig>, current_tick| {
+            client.update(
+                current_tick,
+                &shared,
+                &entities,
+                &worlds,
+                &player_lists,
+                &inventories,
+            );
+            flush(client);
+
+            let mut login = None;
+            let mut updates = vec![];
+
+            conn.for_each_packet(|pkt| match pkt {
+                S2cPlayPacket::LoginPlay(p) => login = Some(p.simulation_distance.0),
+                S2cPlayPacket::SetSimulationDistance(p) => updates.push(p.0 .0),
+                _ => {}
+            });
+
+            (login, updates)
+        };
+
+        assert_eq!(tick(&mut client, 0), (Some(8), vec![]));
+
+        client.set_simulation_distance(1);
+        assert_eq!(client.simulation_distance(), 2);
+        assert_eq!(tick(&mut client, 1), (None, vec![2]));
+
+        assert_eq!(tick(&mut client, 2), (None, vec![]));
+    }
+
+    #[test]
+    fn position_history_keeps_last_positions_in_order() {
+        let shared = setup_server(MockConfig::default()).unwrap();
+        let _guard = shared.tokio_handle().enter();
+
+        let (mut client, _conn) = new_client();
+        let entities = Entities::new();
+        let mut worlds = Worlds::new(shared.clone());
+        let player_lists = PlayerLists::new(None);
+        let inventories = Inventories::new();
+
+        let (world, _) = worlds.insert(DimensionId::default(), ());
+        client.respawn(world);
+
+        let ticks = POSITION_HISTORY_LEN as Ticks + 5;
+
+        for tick in 0..ticks {
+            client.position = Vec3::new(tick as f64, 0.0, 0.0);
+            client.update(
+                tick,
+                &shared,
+                &entities,
+                &worlds,
+                &player_lists,
+                &inventories,
+            );
+        }
+
+        let history: Vec<_> = client.position_history().collect();
+        let expected: Vec<_> = (5..ticks)
+            .map(|tick| (tick, Vec3::new(tick as f64, 0.0, 0.0)))
+            .collect();
+
+        assert_eq!(history, expected);
+    }
+
+    #[test]
+    fn close_container_reports_window_id() {
+        let shared = setup_server(MockConfig::default()).unwrap();
+        let _guard = shared.tokio_handle().enter();
+
+        let (mut client, mut conn) = new_client();
+        let entities = Entities::new();
+        let mut worlds = Worlds::new(shared.clone());
+        let player_lists = PlayerLists::new(None);
+        let mut inventories = Inventories::new();
+
+        let (world, _) = worlds.insert(DimensionId::default(), ());
+        let (inv, _) = inventories.insert(InventoryKind::Generic9x3, "Menu", ());
+
+        client.respawn(world);
+        assert_eq!(client.window_id(), 0);
+
+        client.set_open_inventory(inv);
+        client.update(0, &shared, &entities, &worlds, &player_lists, &inventories);
+
+        let window_id = client.window_id();
+        assert_ne!(window_id, 0);
+
+        let mut opened = None;
+        conn.for_each_packet(|pkt| {
+            if let S2cPlayPacket::OpenScreen(p) = pkt {
+                opened = Some(p.window_id.0);
+            }
+        });
+        assert_eq!(opened, Some(window_id.into()));
+
+        conn.send_packet(&CloseContainerC2s {
+            window_id: window_id as i8,
+        });
+        client.prepare_c2s_packets();
+
+        match client.next_event() {
+            Some(ClientEvent::CloseContainer { window_id: id }) => {
+                assert_eq!(id as u8, window_id);
+            }
+            other => panic!("unexpected event: {other:?}"),
+        }
+    }
+
+    #[test]
+    fn close_screen_closes_open_window_once() {
+        let shared = setup_server(MockConfig::default()).unwrap();
+        let _guard = shared.tokio_handle().enter();
+
+        let (mut client, mut conn) = new_client();
+        let entities = Entities::new();
+        let mut worlds = Worlds::new(shared.clone());
+        let player_lists = PlayerLists::new(None);
+        let mut inventories = Inventories::new();
+
+        let (world, _) = worlds.insert(DimensionId::default(), ());
+        let (inv, _) = inventories.insert(InventoryKind::Generic9x3, "Menu", ());
+
+        client.respawn(world);
+        client.set_open_inventory(inv);
+        client.update(0, &shared, &entities, &worlds, &player_lists, &inventories);
+        conn.for_each_packet(|_| {});
+
+        let window_id = client.window_id();
+
+        client.close_screen();
+        client.close_screen();
+        assert_eq!(client.open_inventory(), InventoryId::NULL);
+
+        // Opening and closing an inventory on the same tick sends nothing.
+        client.set_open_inventory(inv);
+        client.close_screen();
+        client.update(1, &shared, &entities, &worlds, &player_lists, &inventories);
+
+        let mut closed = vec![];
+        conn.for_each_packet(|pkt| match pkt {
+            S2cPlayPacket::CloseContainerS2c(p) => closed.push(p.window_id),
+            S2cPlayPacket::OpenScreen(_) => panic!("window was reopened"),
+            _ => {}
+        });
+
+        assert_eq!(closed, [window_id]);
+    }
+
+    #[test]
+    fn no_player_list_sends_no_player_info() {
+        let shared = setup_server(MockConfig::default()).unwrap();
+        let _guard = shared.tokio_handle().enter();
+
+        let (mut client, mut conn) = new_client();
+        let entities = Entities::new();
+        let mut worlds = Worlds::new(shared.clone());
+        let player_lists = PlayerLists::new(None);
+        let inventories = Inventories::new();
+
+        let (world, _) = worlds.insert(DimensionId::default(), ());
+
+        client.respawn(world);
+        client.set_player_list(None);
+
+        for tick in 0..3 {
+            client.update(
+                tick,
+                &shared,
+                &entities,
+                &worlds,
+                &player_lists,
+                &inventories,
+            );
+            flush(&mut client);
+        }
+
+        assert!(!client.is_disconnected());
+
+        conn.for_each_packet(|pkt| match pkt {
+            S2cPlayPacket::PlayerInfo(_) | S2cPlayPacket::SetTabListHeaderAndFooter(_) => {
+                panic!("unexpected player list packet {pkt:?}")
+            }
+            _ => {}
+        });
+    }
+
+    #[test]
+    fn entity_changing_worlds_is_respawned_for_viewers() {
+        let shared = setup_server(MockConfig::default()).unwrap();
+        let _guard = shared.tokio_handle().enter();
+
+        let (mut viewer_a, mut conn_a) = new_client();
+        let (mut viewer_b, mut conn_b) = new_client();
+        let mut entities = Entities::new();
+        let mut worlds = Worlds::new(shared.clone());
+        let player_lists = PlayerLists::new(None);
+        let inventories = Inventories::new();
+
+        let (world_a, _) = worlds.insert(DimensionId::default(), ());
+        let (world_b, _) = worlds.insert(DimensionId::default(), ());
+
+        let (id, entity) = entities.insert(entity::EntityKind::Zombie, ());
+        entity.set_world(world_a);
+        let raw_id = VarInt(id.to_raw());
+
+        viewer_a.respawn(world_a);
+        viewer_b.respawn(world_b);
+
+        // Returns the number of times the entity was spawned and despawned for
+        // each viewer this tick.
+        let mut tick = |current_tick, entities: &mut Entities<MockConfig>| {
+            update_entity_partition(entities, &mut worlds, None, false, current_tick);
+
+            let mut counts = vec![];
+
+            for (client, conn) in [(&mut viewer_a, &mut conn_a), (&mut viewer_b, &mut conn_b)] {
+                client.update(
+                    current_tick,
+                    &shared,
+                    entities,
+                    &worlds,
+                    &player_lists,
+                    &inventories,
+                );
+                flush(client);
+
+                let mut spawned = 0;
+                let mut despawned = 0;
+
+                conn.for_each_packet(|pkt| match pkt {
+                    S2cPlayPacket::SpawnEntity(p) if p.entity_id == raw_id => spawned += 1,
+                    S2cPlayPacket::RemoveEntities(p) if p.entity_ids.contains(&raw_id) => {
+                        despawned += 1
+                    }
+                    _ => {}
+                });
+
+                counts.push((spawned, despawned));
+            }
+
+            entities.update();
+            worlds.update();
+
+            counts
+        };
+
+        assert_eq!(tick(0, &mut entities), [(1, 0), (0, 0)]);
+
+        entities[id].set_world(world_b);
+        assert_eq!(tick(1, &mut entities), [(0, 1), (1, 0)]);
+
+        // Setting the same world again does not respawn the entity.
+        entities[id].set_world(world_b);
+        assert_eq!(tick(2, &mut entities), [(0, 0), (0, 0)]);
+    }
+
+    #[test]
+    fn respawn_into_invalid_world_is_refused() {
+        let shared = setup_server(MockConfig::default()).unwrap();
+        let _guard = shared.tokio_handle().enter();
+
+        let (mut client, mut conn) = new_client();
+        let entities = Entities::new();
+        let mut worlds = Worlds::new(shared.clone());
+        let player_lists = PlayerLists::new(None);
+        let inventories = Inventories::new();
+
+        let (world, _) = worlds.insert(DimensionId::default(), ());
+        let (removed, _) = worlds.insert(DimensionId::default(), ());
+        worlds.remove(removed);
+
+        client.respawn(world);
+        client.update(0, &shared, &entities, &worlds, &player_lists, &inventories);
+        flush(&mut client);
+        conn.for_each_packet(|_| {});
+
+        client.respawn(removed);
+        client.update(1, &shared, &entities, &worlds, &player_lists, &inventories);
+        flush(&mut client);
+
+        assert!(!client.is_disconnected());
+        assert_eq!(client.world(), world);
+
+        conn.for_each_packet(|pkt| match pkt {
+            S2cPlayPacket::Respawn(_) => panic!("client was respawned"),
+            S2cPlayPacket::DisconnectPlay(_) => panic!("client was disconnected"),
+            _ => {}
+        });
+    }
+
+    #[test]
+    fn entity_outside_entity_view_distance_is_not_spawned() {
+        let shared = setup_server(MockConfig::default()).unwrap();
+        let _guard = shared.tokio_handle().enter();
+
+        let (mut client, mut conn) = new_client();
+        let mut entities = Entities::new();
+        let mut worlds = Worlds::new(shared.clone());
+        let player_lists = PlayerLists::new(None);
+        let inventories = Inventories::new();
+
+        let (world_id, world) = worlds.insert(DimensionId::default(), ());
+        world.chunks.insert([8, 0], UnloadedChunk::default(), ());
+
+        let (near, entity) = entities.insert(entity::EntityKind::Zombie, ());
+        entity.set_world(world_id);
+
+        let (_, entity) = entities.insert(entity::EntityKind::Zombie, ());
+        entity.set_world(world_id);
+        entity.set_position([8.0 * 16.0 + 1.0, 0.0, 1.0]);
+
+        update_entity_partition(&mut entities, &mut worlds, None, false, 0);
+
+        client.respawn(world_id);
+        client.set_view_distance(16);
+        client.set_entity_view_distance(32);
+        assert_eq!(client.entity_view_distance(), 16);
+        client.set_entity_view_distance(4);
+        assert_eq!(client.entity_view_distance(), 4);
+
+        client.update(0, &shared, &entities, &worlds, &player_lists, &inventories);
+        flush(&mut client);
+
+        let mut spawned = vec![];
+        let mut chunk_loaded = false;
+
+        conn.for_each_packet(|pkt| match pkt {
+            S2cPlayPacket::SpawnEntity(p) => spawned.push(p.entity_id.0),
+            S2cPlayPacket::ChunkDataAndUpdateLight(p) if p.chunk_x == 8 && p.chunk_z == 0 => {
+                chunk_loaded = true
+            }
+            _ => {}
+        });
+
+        assert!(chunk_loaded);
+        assert_eq!(spawned, [near.to_raw()]);
+    }
+
+    #[test]
+    fn entity_velocity_targets_entity_in_view() {
+        let shared = setup_server(MockConfig::default()).unwrap();
+        let _guard = shared.tokio_handle().enter();
+
+        let (mut client, mut conn) = new_client();
+        let mut entities = Entities::new();
+        let mut worlds = Worlds::new(shared.clone());
+
+        let (world_id, _) = worlds.insert(DimensionId::default(), ());
+        client.respawn(world_id);
+
+        let (near, entity) = entities.insert(entity::EntityKind::Zombie, ());
+        entity.set_world(world_id);
+        entity.set_position([3.0, 0.0, 5.0]);
+
+        let (far, entity) = entities.insert(entity::EntityKind::Zombie, ());
+        entity.set_world(world_id);
+        entity.set_position([10_000.0, 0.0, 0.0]);
+
+        assert!(client.set_entity_velocity(&entities, near, [1.0, 2.0, -0.5]));
+        assert!(!client.set_entity_velocity(&entities, far, [1.0, 0.0, 0.0]));
+        flush(&mut client);
+
+        let mut velocities = vec![];
+
+        conn.for_each_packet(|pkt| {
+            if let S2cPlayPacket::SetEntityVelocity(p) = pkt {
+                velocities.push((p.entity_id.0, p.velocity));
+            }
+        });
+
+        assert_eq!(velocities, [(near.to_raw(), [400, 800, -200])]);
+    }
+
+    #[test]
+    fn deleted_world_with_fallback_moves_client() {
+        let shared = setup_server(MockConfig::default()).unwrap();
+        let _guard = shared.tokio_handle().enter();
+
+        let (mut client, mut conn) = new_client();
+        let entities = Entities::new();
+        let mut worlds = Worlds::new(shared.clone());
+        let player_lists = PlayerLists::new(None);
+        let inventories = Inventories::new();
+
+        let (arena, _) = worlds.insert(DimensionId::default(), ());
+        let (lobby, _) = worlds.insert(DimensionId::default(), ());
+
+        client.respawn(arena);
+        client.update(0, &shared, &entities, &worlds, &player_lists, &inventories);
+        flush(&mut client);
+        conn.for_each_packet(|_| {});
+
+        assert!(worlds.delete_with_fallback(arena, lobby, [1.0, 64.0, 2.0]));
+        client.update(1, &shared, &entities, &worlds, &player_lists, &inventories);
+        flush(&mut client);
+
+        assert!(!client.is_disconnected());
+        assert_eq!(client.world(), lobby);
+        assert_eq!(client.position(), Vec3::new(1.0, 64.0, 2.0));
+
+        let mut respawned = false;
+        let mut teleported = false;
+
+        conn.for_each_packet(|pkt| match pkt {
+            S2cPlayPacket::Respawn(_) => respawned = true,
+            S2cPlayPacket::SynchronizePlayerPosition(p) => {
+                assert!(respawned, "position must be set after respawning");
+                assert_eq!(p.position, [1.0, 64.0, 2.0]);
+                teleported = true;
+            }
+            S2cPlayPacket::DisconnectPlay(_) => panic!("client was disconnected"),
+            _ => {}
+        });
+
+        assert!(respawned);
+        assert!(teleported);
+    }
+
+    #[test]
+    fn invalid_fallback_world_does_not_teleport_client() {
+        let shared = setup_server(MockConfig::default()).unwrap();
+        let _guard = shared.tokio_handle().enter();
+
+        let (mut client, mut conn) = new_client();
+        let entities = Entities::new();
+        let mut worlds = Worlds::new(shared.clone());
+        let player_lists = PlayerLists::new(None);
+        let inventories = Inventories::new();
+
+        let (lobby, _) = worlds.insert(DimensionId::default(), ());
+        let (arena, _) = worlds.insert(DimensionId::default(), ());
+        let (ghost, _) = worlds.insert(DimensionId::default(), ());
+
+        client.respawn(lobby);
+        client.update(0, &shared, &entities, &worlds, &player_lists, &inventories);
+        flush(&mut client);
+        conn.for_each_packet(|_| {});
+
+        // The fallback of the arena no longer exists.
+        assert!(worlds.remove(ghost).is_some());
+        assert!(worlds.delete_with_fallback(arena, ghost, [1.0, 64.0, 2.0]));
+
+        let pos = client.position();
+
+        client.respawn(arena);
+        client.update(1, &shared, &entities, &worlds, &player_lists, &inventories);
+        flush(&mut client);
+
+        assert!(!client.is_disconnected());
+        assert_eq!(client.world(), lobby);
+        assert_eq!(client.position(), pos);
+
+        conn.for_each_packet(|pkt| match pkt {
+            S2cPlayPacket::Respawn(_) => panic!("client was respawned"),
+            S2cPlayPacket::SynchronizePlayerPosition(_) => panic!("client was teleported"),
+            S2cPlayPacket::DisconnectPlay(_) => panic!("client was disconnected"),
+            _ => {}
+        });
+    }
+
+    #[test]
+    fn vehicle_entering_view_is_sent_with_passengers() {
+        let shared = setup_server(MockConfig::default()).unwrap();
+        let _guard = shared.tokio_handle().enter();
+
+        let (mut client, mut conn) = new_client();
+        let mut entities = Entities::new();
+        let mut worlds = Worlds::new(shared.clone());
+        let player_lists = PlayerLists::new(None);
+        let inventories = Inventories::new();
+
+        let (world_id, _) = worlds.insert(DimensionId::default(), ());
+        client.respawn(world_id);
+
+        let (boat, entity) = entities.insert(entity::EntityKind::Boat, ());
+        entity.set_world(world_id);
+        entity.set_position([1000.0, 64.0, 1000.0]);
+
+        let (rider, entity) = entities.insert(entity::EntityKind::Zombie, ());
+        entity.set_world(world_id);
+
+        entities[boat].set_passengers(vec![rider]);
+
+        let mut packets = vec![];
+
+        for tick in 0..2 {
+            if tick == 1 {
+                // Drive the boat into view from a cell the client can't see.
+                entities[boat].set_position([5.0, 64.0, 5.0]);
+            }
+
+            entities.update_passengers();
+            update_entity_partition(&mut entities, &mut worlds, None, false, tick);
+            client.update(
+                tick,
+                &shared,
+                &entities,
+                &worlds,
+                &player_lists,
+                &inventories,
+            );
+            flush(&mut client);
+
+            conn.for_each_packet(|pkt| match pkt {
+                S2cPlayPacket::SpawnEntity(p) if tick == 1 => packets.push(("spawn", p.entity_id)),
+                S2cPlayPacket::SetPassengers(p) if tick == 1 => {
+                    assert_eq!(p.passengers, [VarInt(rider.to_raw())]);
+                    packets.push(("passengers", p.entity_id));
+                }
+                _ => {}
+            });
+
+            entities.update();
+            worlds.update();
+        }
+
+        assert_eq!(packets.len(), 3, "{packets:?}");
+        assert_eq!(packets[2], ("passengers", VarInt(boat.to_raw())));
+    }
+
+    /// Spawns `count` entities and the client's own player entity in a world,
+    /// then joins a new client to it. Returns the number of entities and
+    /// players spawned for the client. If `cached` is set, the init packets
+    /// of the cells the client loads are cached before the update.
+    fn spawn_entities_for_new_viewer(count: usize, cached: bool) -> (usize, usize) {
+        let shared = setup_server(MockConfig::default()).unwrap();
+        let _guard = shared.tokio_handle().enter();
+
+        let (mut client, mut conn) = new_client();
+        let mut entities = Entities::new();
+        let mut worlds = Worlds::new(shared.clone());
+        let player_lists = PlayerLists::new(None);
+        let inventories = Inventories::new();
+
+        let (world_id, _) = worlds.insert(DimensionId::default(), ());
+
+        for i in 0..count {
+            let (_, entity) = entities.insert(entity::EntityKind::Zombie, ());
+            entity.set_world(world_id);
+            entity.set_position([(i % 64) as f64 * 2.0, 0.0, (i / 64) as f64 * 2.0]);
+        }
+
+        let (_, entity) = entities
+            .insert_with_uuid(entity::EntityKind::Player, client.uuid(), ())
+            .unwrap();
+        entity.set_world(world_id);
+
+        update_entity_partition(&mut entities, &mut worlds, None, false, 0);
+
+        client.respawn(world_id);
+
+        if cached {
+            client.request_entity_init_packets(&worlds);
+            cache_entity_init_packets(&entities, &mut worlds, None);
+        }
+
+        client.update(0, &shared, &entities, &worlds, &player_lists, &inventories);
+        flush(&mut client);
+
+        let mut spawned = 0;
+        let mut players = 0;
+
+        conn.for_each_packet(|pkt| match pkt {
+            S2cPlayPacket::SpawnEntity(_) => spawned += 1,
+            S2cPlayPacket::SpawnPlayer(_) => players += 1,
+            _ => {}
+        });
+
+        (spawned, players)
+    }
+
+    #[test]
+    fn new_viewer_spawns_every_entity_but_itself() {
+        for cached in [false, true] {
+            let (spawned, players) = spawn_entities_for_new_viewer(500, cached);
+
+            assert_eq!(spawned, 500);
+            // The client's own player entity is the only player in the world.
+            assert_eq!(players, 0);
+        }
+    }
+
+    #[test]
+    fn update_error_sends_disconnect_reason() {
+        let shared = setup_server(MockConfig::default()).unwrap();
+        let _guard = shared.tokio_handle().enter();
+
+        let (mut client, mut conn) = new_client();
+        let entities = Entities::new();
+        let worlds = Worlds::new(shared.clone());
+        let player_lists = PlayerLists::new(None);
+        let inventories = Inventories::new();
+
+        // The client was never spawned in a world, so updating it fails.
+        client.update(0, &shared, &entities, &worlds, &player_lists, &inventories);
+
+        assert!(client.is_disconnected());
+
+        let mut reason = None;
+
+        conn.for_each_packet(|pkt| {
+            if let S2cPlayPacket::DisconnectPlay(pkt) = pkt {
+                reason = Some(pkt.reason);
+            }
+        });
+
+        let reason = reason.expect("no disconnect packet was sent");
+        assert!(!reason.is_empty());
+        assert_eq!(reason, INTERNAL_ERROR_REASON.into());
+    }
+
+    #[test]
+    fn broadcast_entity_event_reaches_viewers() {
+        let shared = setup_server(MockConfig::default()).unwrap();
+        let _guard = shared.tokio_handle().enter();
+
+        let (mut owner, mut owner_conn) = new_client();
+        let (mut viewer, mut viewer_conn) = new_client();
+        viewer.uuid = Uuid::from_bytes([2; 16]);
+
+        let mut entities = Entities::new();
+        let mut worlds = Worlds::new(shared.clone());
+        let player_lists = PlayerLists::new(None);
+        let inventories = Inventories::new();
+
+        let (world_id, _) = worlds.insert(DimensionId::default(), ());
+        viewer.respawn(world_id);
+
+        let event = entity::EntityEvent::UseTotemOfUndying;
+        assert!(!owner.broadcast_entity_event(&mut entities, event));
+
+        let (id, entity) = entities
+            .insert_with_uuid(entity::EntityKind::Player, owner.uuid(), ())
+            .unwrap();
+        entity.set_world(world_id);
+
+        let mut viewer_events = vec![];
+
+        for tick in 0..2 {
+            if tick == 1 {
+                assert!(owner.broadcast_entity_event(&mut entities, event));
+            }
+
+            update_entity_partition(&mut entities, &mut worlds, None, false, tick);
+            viewer.update(
+                tick,
+                &shared,
+                &entities,
+                &worlds,
+                &player_lists,
+                &inventories,
+            );
+            flush(&mut viewer);
+
+            viewer_conn.for_each_packet(|pkt| {
+                if let S2cPlayPacket::EntityEvent(p) = pkt {
+                    viewer_events.push((tick, p.entity_id));
+                }
+            });
+
+            entities.update();
+            worlds.update();
+        }
+
+        assert_eq!(viewer_events, [(1, id.to_raw())]);
+
+        flush(&mut owner);
+
+        let mut owner_events = vec![];
+
+        owner_conn.for_each_packet(|pkt| {
+            if let S2cPlayPacket::EntityEvent(p) = pkt {
+                owner_events.push(p.entity_id);
+            }
+        });
+
+        // The owner sees its own player entity as entity 0.
+        assert_eq!(owner_events, [0]);
+    }
+
+    #[test]
+    fn broadcast_player_field_reaches_viewers() {
+        let shared = setup_server(MockConfig::default()).unwrap();
+        let _guard = shared.tokio_handle().enter();
+
+        let (mut owner, _owner_conn) = new_client();
+        let (mut viewer, mut viewer_conn) = new_client();
+        viewer.uuid = Uuid::from_bytes([2; 16]);
+
+        let mut entities = Entities::new();
+        let mut worlds = Worlds::new(shared.clone());
+        let player_lists = PlayerLists::new(None);
+        let inventories = Inventories::new();
+
+        let (world_id, _) = worlds.insert(DimensionId::default(), ());
+        viewer.respawn(world_id);
+
+        let (id, entity) = entities
+            .insert_with_uuid(entity::EntityKind::Player, owner.uuid(), ())
+            .unwrap();
+        entity.set_world(world_id);
+        let raw_id = VarInt(id.to_raw());
+
+        let name = Text::from("Broadcast");
+        owner.player_mut().set_custom_name(Some(name.clone()));
+        owner.player_mut().set_glowing(true);
+        owner.set_player_field_broadcast(PlayerDataField::CustomName, true);
+
+        assert!(owner.player_field_broadcast(PlayerDataField::CustomName));
+        assert!(!owner.player_field_broadcast(PlayerDataField::Flags));
+
+        let mut metadata = vec![];
+
+        for tick in 0..2 {
+            if tick == 1 {
+                owner.update_broadcast_player_data(&mut entities);
+            }
+
+            update_entity_partition(&mut entities, &mut worlds, None, false, tick);
+            viewer.update(
+                tick,
+                &shared,
+                &entities,
+                &worlds,
+                &player_lists,
+                &inventories,
+            );
+            flush(&mut viewer);
+
+            viewer_conn.for_each_packet(|pkt| {
+                if let S2cPlayPacket::SetEntityMetadata(p) = pkt {
+                    if p.entity_id == raw_id {
+                        metadata.push(p.metadata.0.to_vec());
+                    }
+                }
+            });
+
+            entities.update();
+            worlds.update();
+        }
+
+        let Some(TrackedData::Player(data)) = entities.get(id).map(|e| e.data()) else {
+            panic!("missing player entity")
+        };
+
+        assert_eq!(data.get_custom_name(), Some(&name));
+        assert!(!data.get_glowing());
+
+        // Only the custom name (index 2) was sent to the viewer.
+        assert_eq!(metadata.len(), 1);
+        assert_eq!(metadata[0][0], 2);
+        assert_eq!(metadata[0].last(), Some(&0xff));
+    }
+
+    #[test]
+    fn crossing_chunk_border_counts_loads_and_unloads() {
+        let shared = setup_server(MockConfig::default()).unwrap();
+        let _guard = shared.tokio_handle().enter();
+
+        let (mut client, mut conn) = new_client();
+        let mut entities = Entities::new();
+        let mut worlds = Worlds::new(shared.clone());
+        let player_lists = PlayerLists::new(None);
+        let inventories = Inventories::new();
+
+        let (world_id, world) = worlds.insert(DimensionId::default(), ());
+        for x in -8..=8 {
+            for z in -8..=8 {
+                world.chunks.insert([x, z], UnloadedChunk::default(), ());
+            }
+        }
+
+        client.respawn(world_id);
+
+        // Returns the client's load and unload counters after checking them
+        // against the packets sent this tick.
+        let mut tick = |client: &mut Client<MockConfig>, current_tick| {
+            update_entity_partition(&mut entities, &mut worlds, None, false, current_tick);
+            client.update(
+                current_tick,
+                &shared,
+                &entities,
+                &worlds,
+                &player_lists,
+                &inventories,
+            );
+            flush(client);
+
+            let mut packets = (0, 0);
+
+            conn.for_each_packet(|pkt| match pkt {
+                S2cPlayPacket::ChunkDataAndUpdateLight(_) => packets.0 += 1,
+                S2cPlayPacket::UnloadChunk(_) => packets.1 += 1,
+                _ => {}
+            });
+
+            let counts = (
+                client.chunks_loaded_this_tick(),
+                client.chunks_unloaded_this_tick(),
+            );
+            assert_eq!(counts, packets);
+
+            entities.update();
+            worlds.update();
+
+            counts
+        };
+
+        let (initial, _) = tick(&mut client, 0);
+        assert!(initial > 0);
+
+        // Staying in the same chunk resets the counters.
+        assert_eq!(tick(&mut client, 1), (0, 0));
+
+        client.teleport([16.0, 0.0, 0.0], 0.0, 0.0);
+        let (loaded, unloaded) = tick(&mut client, 2);
+        assert!(loaded > 0);
+        assert_eq!(loaded, unloaded);
+
+        // Crossing back churns the same number of chunks.
+        client.teleport([15.0, 0.0, 0.0], 0.0, 0.0);
+        assert_eq!(tick(&mut client, 3), (loaded, unloaded));
+    }
 
-                send.append_packet(&OpenScreen {
-                    window_id: VarInt(self.window_id.into()),
-                    window_type: VarInt(inv.kind() as i32),
-                    window_title: inv.title().clone(),
-                })?;
+    #[test]
+    fn limbo_stops_chunks_but_keeps_alive() {
+        let shared = setup_server(MockConfig::default()).unwrap();
+        let _guard = shared.tokio_handle().enter();
 
-                send.append_packet(&SetContainerContentEncode {
-                    window_id: self.window_id,
-                    state_id: VarInt(self.inv_state_id.0),
-                    slots: inv.slot_slice(),
-                    carried_item: &self.cursor_item,
-                })?;
+        let (mut client, mut conn) = new_client();
+        let mut entities = Entities::new();
+        let mut worlds = Worlds::new(shared.clone());
+        let player_lists = PlayerLists::new(None);
+        let inventories = Inventories::new();
+
+        let (world_id, world) = worlds.insert(DimensionId::default(), ());
+        world.chunks.insert([0, 0], UnloadedChunk::default(), ());
+
+        client.respawn(world_id);
+
+        // Returns the number of chunks loaded, chunks unloaded and keepalives
+        // sent this tick.
+        let mut tick = |client: &mut Client<MockConfig>, current_tick| {
+            update_entity_partition(&mut entities, &mut worlds, None, false, current_tick);
+            client.update(
+                current_tick,
+                &shared,
+                &entities,
+                &worlds,
+                &player_lists,
+                &inventories,
+            );
+            flush(client);
+
+            let mut counts = (0, 0, 0);
+
+            conn.for_each_packet(|pkt| match pkt {
+                S2cPlayPacket::ChunkDataAndUpdateLight(_) => counts.0 += 1,
+                S2cPlayPacket::UnloadChunk(_) => counts.1 += 1,
+                S2cPlayPacket::KeepAliveS2c(_) => counts.2 += 1,
+                _ => {}
+            });
+
+            entities.update();
+            worlds.update();
+
+            counts
+        };
+
+        assert_eq!(tick(&mut client, 0), (1, 0, 1));
+
+        client.enter_limbo();
+        assert!(client.in_limbo());
+        assert_eq!(tick(&mut client, 1), (0, 1, 0));
+
+        // Moving does not stream chunks while in limbo.
+        client.teleport([64.0, 0.0, 64.0], 0.0, 0.0);
+        assert_eq!(tick(&mut client, 2), (0, 0, 0));
+        client.teleport([0.0, 0.0, 0.0], 0.0, 0.0);
+
+        // Pretend the client responded to the first keepalive.
+        client.bits.set_got_keepalive(true);
+        let keepalive_tick = shared.tick_rate() * 10;
+        assert_eq!(tick(&mut client, keepalive_tick), (0, 0, 1));
+        assert!(!client.is_disconnected());
+
+        client.leave_limbo();
+        assert_eq!(tick(&mut client, keepalive_tick + 1), (1, 0, 0));
+    }
+
+    struct KeepaliveConfig;
+
+    impl Config for KeepaliveConfig {
+        type ServerState = ();
+        type ClientState = ();
+        type EntityState = ();
+        type WorldState = ();
+        type ChunkState = ();
+        type PlayerListState = ();
+        type InventoryState = ();
+
+        fn keepalive_interval(&self) -> Ticks {
+            20
+        }
+
+        fn max_missed_keepalives(&self) -> u32 {
+            2
+        }
+    }
+
+    struct ManualChunksConfig;
+
+    impl Config for ManualChunksConfig {
+        type ServerState = ();
+        type ClientState = ();
+        type EntityState = ();
+        type WorldState = ();
+        type ChunkState = ();
+        type PlayerListState = ();
+        type InventoryState = ();
+
+        fn chunk_load_assertions(&self) -> bool {
+            false
+        }
+    }
+
+    #[test]
+    fn chunk_loaded_twice_does_not_panic_without_assertions() {
+        let shared = setup_server(ManualChunksConfig).unwrap();
+        let _guard = shared.tokio_handle().enter();
+
+        let (mut client, mut conn) = new_client_with_config();
+        let entities = Entities::new();
+        let mut worlds = Worlds::new(shared.clone());
+        let player_lists = PlayerLists::new(None);
+        let inventories = Inventories::new();
+
+        let (world_id, world) = worlds.insert(DimensionId::default(), ());
+        world.chunks.insert([0, 0], UnloadedChunk::default(), ());
+
+        // Pretend the chunk was already sent to the client by other means.
+        #[cfg(debug_assertions)]
+        client.loaded_chunks.insert(ChunkPos::new(0, 0));
+
+        client.respawn(world_id);
+        client.update(0, &shared, &entities, &worlds, &player_lists, &inventories);
+        client.send.as_mut().unwrap().flush().unwrap();
+
+        let mut chunk_loaded = false;
+
+        conn.for_each_packet(|pkt| {
+            if let S2cPlayPacket::ChunkDataAndUpdateLight(p) = pkt {
+                chunk_loaded |= p.chunk_x == 0 && p.chunk_z == 0;
             }
-        } else {
-            // Update an already open window.
-            if let Some(inv) = inventories.get(self.open_inventory) {
-                inv.send_update(send, self.window_id, &mut self.inv_state_id)?;
+        });
+
+        assert!(chunk_loaded);
+        assert!(!client.is_disconnected());
+    }
+
+    #[test]
+    fn missed_keepalives_disconnect_after_threshold() {
+        let shared = setup_server(KeepaliveConfig).unwrap();
+        let _guard = shared.tokio_handle().enter();
+
+        let (mut client, mut conn) = new_client_with_config();
+        let entities = Entities::new();
+        let mut worlds = Worlds::new(shared.clone());
+        let player_lists = PlayerLists::new(None);
+        let inventories = Inventories::new();
+
+        let (world_id, _) = worlds.insert(DimensionId::default(), ());
+        client.respawn(world_id);
+
+        // Returns the number of keepalives sent this tick.
+        let mut tick = |client: &mut Client<KeepaliveConfig>, current_tick| {
+            client.update(
+                current_tick,
+                &shared,
+                &entities,
+                &worlds,
+                &player_lists,
+                &inventories,
+            );
+            if let Some(send) = &mut client.send {
+                send.flush().unwrap();
+            }
+
+            let mut keepalives = 0;
+            conn.for_each_packet(|pkt| {
+                if let S2cPlayPacket::KeepAliveS2c(_) = pkt {
+                    keepalives += 1;
+                }
+            });
+
+            keepalives
+        };
+
+        assert_eq!(tick(&mut client, 0), 1);
+
+        // The first two missed intervals are tolerated without resending.
+        assert_eq!(tick(&mut client, 20), 0);
+        assert_eq!(tick(&mut client, 40), 0);
+        assert!(!client.is_disconnected());
+
+        // A late response resets the count.
+        client.bits.set_got_keepalive(true);
+        assert_eq!(tick(&mut client, 60), 1);
+
+        assert_eq!(tick(&mut client, 80), 0);
+        assert_eq!(tick(&mut client, 100), 0);
+        assert!(!client.is_disconnected());
+
+        tick(&mut client, 120);
+        assert!(client.is_disconnected());
+    }
+
+    #[cfg(feature = "packet_capture")]
+    #[tokio::test]
+    async fn packet_capture_records_both_directions() {
+        use valence_protocol::packets::C2sPlayPacket;
+
+        use crate::packet_capture::{read_capture, PacketDirection};
+
+        let path =
+            std::env::temp_dir().join(format!("valence-packet-capture-{}.bin", std::process::id()));
+
+        let (mut client, mut conn) = new_client();
+
+        client.start_capture(&path).unwrap();
+
+        client.send_message("hello");
+        flush(&mut client);
+        conn.send_packet(&ResourcePackC2s::SuccessfullyLoaded);
+        client.prepare_c2s_packets();
+        assert!(client.next_event().is_some());
+
+        client.stop_capture().unwrap();
+
+        // Packets after the capture is stopped are not recorded.
+        client.send_message("goodbye");
+
+        let packets = read_capture(std::fs::File::open(&path).unwrap()).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(packets.len(), 2);
+
+        assert_eq!(packets[0].direction, PacketDirection::Clientbound);
+        assert!(matches!(
+            S2cPlayPacket::decode(&mut packets[0].data.as_slice()).unwrap(),
+            S2cPlayPacket::SystemChatMessage(_)
+        ));
+
+        assert_eq!(packets[1].direction, PacketDirection::Serverbound);
+        assert!(matches!(
+            C2sPlayPacket::decode(&mut packets[1].data.as_slice()).unwrap(),
+            C2sPlayPacket::ResourcePackC2s(ResourcePackC2s::SuccessfullyLoaded)
+        ));
+        assert!(packets[0].elapsed <= packets[1].elapsed);
+    }
+
+    #[test]
+    fn auto_respawn_moves_killed_client_to_spawn() {
+        let shared = setup_server(MockConfig::default()).unwrap();
+        let _guard = shared.tokio_handle().enter();
+
+        let (mut client, mut conn) = new_client();
+        let entities = Entities::new();
+        let mut worlds = Worlds::new(shared.clone());
+        let player_lists = PlayerLists::new(None);
+        let inventories = Inventories::new();
+
+        let (world_id, _) = worlds.insert(DimensionId::default(), ());
+
+        client.respawn(world_id);
+        client.update(0, &shared, &entities, &worlds, &player_lists, &inventories);
+        conn.for_each_packet(|_| {});
+
+        client.set_auto_respawn(true);
+        client.set_spawn_position([10, 64, -3], 90.0);
+        client.teleport([100.0, 0.0, 100.0], 0.0, 0.0);
+        client.kill(None, "Oops");
+        client.update(1, &shared, &entities, &worlds, &player_lists, &inventories);
+        flush(&mut client);
+
+        let mut packets = vec![];
+
+        conn.for_each_packet(|pkt| match pkt {
+            S2cPlayPacket::CombatDeath(_) => packets.push("death"),
+            S2cPlayPacket::Respawn(_) => packets.push("respawn"),
+            S2cPlayPacket::SynchronizePlayerPosition(p) if p.position == [10.5, 64.0, -2.5] => {
+                assert_eq!(p.yaw, 90.0);
+                packets.push("spawn teleport");
+            }
+            _ => {}
+        });
+
+        assert_eq!(packets, ["death", "respawn", "spawn teleport"]);
+        assert_eq!(client.position(), Vec3::new(10.5, 64.0, -2.5));
+
+        // Only the teleport to the spawn position is sent when respawning.
+        client.kill(None, "Oops again");
+        client.update(2, &shared, &entities, &worlds, &player_lists, &inventories);
+        flush(&mut client);
+
+        let mut teleports = vec![];
+
+        conn.for_each_packet(|pkt| {
+            if let S2cPlayPacket::SynchronizePlayerPosition(p) = pkt {
+                teleports.push(p.position);
+            }
+        });
+
+        assert_eq!(teleports, [[10.5, 64.0, -2.5]]);
+    }
+
+    #[test]
+    fn auto_respawn_handles_respawn_request_after_health_reaches_zero() {
+        let shared = setup_server(MockConfig::default()).unwrap();
+        let _guard = shared.tokio_handle().enter();
+
+        let (mut client, mut conn) = new_client();
+        let entities = Entities::new();
+        let mut worlds = Worlds::new(shared.clone());
+        let player_lists = PlayerLists::new(None);
+        let inventories = Inventories::new();
+
+        let (world_id, _) = worlds.insert(DimensionId::default(), ());
+
+        client.respawn(world_id);
+        client.update(0, &shared, &entities, &worlds, &player_lists, &inventories);
+        conn.for_each_packet(|_| {});
+
+        client.set_auto_respawn(true);
+        client.set_spawn_position([10, 64, -3], 90.0);
+        client.teleport([100.0, 0.0, 100.0], 0.0, 0.0);
+        client.set_health_and_food(0.0, 20, 5.0);
+        client.update(1, &shared, &entities, &worlds, &player_lists, &inventories);
+        flush(&mut client);
+        conn.for_each_packet(|_| {});
+
+        // The client died on its own and clicked the respawn button.
+        conn.send_packet(&ClientCommand::PerformRespawn);
+        client.prepare_c2s_packets();
+
+        assert!(matches!(
+            client.next_event(),
+            Some(ClientEvent::PerformRespawn)
+        ));
+        assert_eq!(client.position(), Vec3::new(10.5, 64.0, -2.5));
+
+        client.update(2, &shared, &entities, &worlds, &player_lists, &inventories);
+        flush(&mut client);
+
+        let mut packets = vec![];
+
+        conn.for_each_packet(|pkt| match pkt {
+            S2cPlayPacket::Respawn(_) => packets.push("respawn"),
+            S2cPlayPacket::SynchronizePlayerPosition(p) if p.position == [10.5, 64.0, -2.5] => {
+                packets.push("spawn teleport");
+            }
+            _ => {}
+        });
+
+        assert_eq!(packets, ["respawn", "spawn teleport"]);
+    }
+
+    #[test]
+    fn flush_packets_waits_for_login() {
+        let shared = setup_server(MockConfig::default()).unwrap();
+        let _guard = shared.tokio_handle().enter();
+
+        let (mut client, mut conn) = new_client();
+        let entities = Entities::new();
+        let mut worlds = Worlds::new(shared.clone());
+        let player_lists = PlayerLists::new(None);
+        let inventories = Inventories::new();
+
+        let (world_id, _) = worlds.insert(DimensionId::default(), ());
+
+        client.respawn(world_id);
+        client.send_message("hello");
+        client.flush_packets();
+
+        conn.for_each_packet(|pkt| panic!("packet {pkt:?} sent before login"));
+
+        client.update(0, &shared, &entities, &worlds, &player_lists, &inventories);
+
+        let mut first = None;
+        conn.for_each_packet(|pkt| {
+            if first.is_none() {
+                first = Some(matches!(pkt, S2cPlayPacket::LoginPlay(_)));
             }
+        });
+
+        assert_eq!(first, Some(true));
+    }
+
+    #[test]
+    fn deferred_flush_batches_packets_until_flushed() {
+        let shared = setup_server(MockConfig::default()).unwrap();
+        let _guard = shared.tokio_handle().enter();
+
+        let (mut client, mut conn) = new_client();
+        let entities = Entities::new();
+        let mut worlds = Worlds::new(shared.clone());
+        let player_lists = PlayerLists::new(None);
+        let inventories = Inventories::new();
+
+        let (world_id, _) = worlds.insert(DimensionId::default(), ());
+
+        client.respawn(world_id);
+        client.update(0, &shared, &entities, &worlds, &player_lists, &inventories);
+        conn.for_each_packet(|_| {});
+
+        client.set_defer_flush(true);
+
+        let mut messages = vec![];
+
+        for tick in 1..=3 {
+            client.send_message(format!("tick {tick}"));
+            client.update(
+                tick,
+                &shared,
+                &entities,
+                &worlds,
+                &player_lists,
+                &inventories,
+            );
+
+            conn.for_each_packet(|_| panic!("packet sent while flushing is deferred"));
         }
 
-        self.old_world = self.world;
-        self.old_position = self.position;
-        self.old_view_distance = self.view_distance;
-        self.player_data.clear_modifications();
+        client.flush_packets();
+
+        conn.for_each_packet(|pkt| {
+            if let S2cPlayPacket::SystemChatMessage(p) = pkt {
+                messages.push(p.chat);
+            }
+        });
 
-        send.flush().context("failed to flush packet queue")?;
+        let expected: Vec<Text> = (1..=3).map(|t| format!("tick {t}").into()).collect();
+        assert_eq!(messages, expected);
+    }
 
-        Ok(())
+    #[test]
+    fn spawn_at_on_join_tick_teleports_after_login() {
+        let shared = setup_server(MockConfig::default()).unwrap();
+        let _guard = shared.tokio_handle().enter();
+
+        let (mut client, mut conn) = new_client();
+        let entities = Entities::new();
+        let mut worlds = Worlds::new(shared.clone());
+        let player_lists = PlayerLists::new(None);
+        let inventories = Inventories::new();
+
+        let (world_id, _) = worlds.insert(DimensionId::default(), ());
+
+        client.spawn_at(world_id, [1.5, 64.0, -2.5], 90.0, 10.0, GameMode::Creative);
+        client.update(0, &shared, &entities, &worlds, &player_lists, &inventories);
+        flush(&mut client);
+
+        let mut packets = vec![];
+
+        conn.for_each_packet(|pkt| match pkt {
+            S2cPlayPacket::LoginPlay(p) => {
+                assert_eq!(p.game_mode, GameMode::Creative);
+                packets.push("login");
+            }
+            S2cPlayPacket::SynchronizePlayerPosition(p) => {
+                assert_eq!(p.position, [1.5, 64.0, -2.5]);
+                assert_eq!((p.yaw, p.pitch), (90.0, 10.0));
+                packets.push("teleport");
+            }
+            S2cPlayPacket::Respawn(_) => packets.push("respawn"),
+            _ => {}
+        });
+
+        assert_eq!(packets, ["login", "teleport"]);
+        assert_eq!(client.world(), world_id);
+        assert_eq!(client.game_mode(), GameMode::Creative);
+    }
+
+    #[test]
+    fn world_sounds_reach_only_clients_in_range() {
+        let shared = setup_server(MockConfig::default()).unwrap();
+        let _guard = shared.tokio_handle().enter();
+
+        let entities = Entities::new();
+        let mut worlds = Worlds::new(shared.clone());
+        let player_lists = PlayerLists::new(None);
+        let inventories = Inventories::new();
+
+        let (world_id, _) = worlds.insert(DimensionId::default(), ());
+
+        let (mut near, mut near_conn) = new_client();
+        let (mut far, mut far_conn) = new_client();
+
+        near.spawn_at(world_id, [0.0, 0.0, 0.0], 0.0, 0.0, GameMode::Survival);
+        far.spawn_at(world_id, [100.0, 0.0, 0.0], 0.0, 0.0, GameMode::Survival);
+
+        let world = &mut worlds[world_id];
+        let quiet = Ident::new("entity.cow.ambient").unwrap();
+        let loud = Ident::new("entity.generic.explode").unwrap();
+        world.play_sound([10.0, 0.0, 0.0], quiet, SoundCategory::Neutral, 1.0, 1.0);
+        world.play_sound([10.0, 0.0, 0.0], loud, SoundCategory::Block, 10.0, 1.0);
+
+        let heard = |client: &mut Client<MockConfig>,
+                     conn: &mut MockConnection,
+                     worlds: &Worlds<MockConfig>| {
+            client.update(0, &shared, &entities, worlds, &player_lists, &inventories);
+            flush(client);
+
+            let mut sounds = vec![];
+            conn.for_each_packet(|pkt| {
+                if let S2cPlayPacket::CustomSoundEffect(p) = pkt {
+                    assert_eq!(p.position, [80, 0, 0]);
+                    sounds.push(p.name.to_string());
+                }
+            });
+            sounds
+        };
+
+        assert_eq!(
+            heard(&mut near, &mut near_conn, &worlds),
+            [
+                "minecraft:entity.cow.ambient",
+                "minecraft:entity.generic.explode"
+            ]
+        );
+        assert_eq!(
+            heard(&mut far, &mut far_conn, &worlds),
+            ["minecraft:entity.generic.explode"]
+        );
+
+        // Sounds are only sent on the tick they are played.
+        worlds.update();
+        assert!(heard(&mut near, &mut near_conn, &worlds).is_empty());
+    }
+
+    #[test]
+    fn cleared_death_location_is_sent_on_respawn() {
+        let shared = setup_server(MockConfig::default()).unwrap();
+        let _guard = shared.tokio_handle().enter();
+
+        let (mut client, mut conn) = new_client();
+        let entities = Entities::new();
+        let mut worlds = Worlds::new(shared.clone());
+        let player_lists = PlayerLists::new(None);
+        let inventories = Inventories::new();
+
+        let (world_id, _) = worlds.insert(DimensionId::default(), ());
+
+        client.respawn(world_id);
+        client.set_death_location(Some((DimensionId::default(), BlockPos::new(1, 2, 3))));
+
+        client.update(0, &shared, &entities, &worlds, &player_lists, &inventories);
+        flush(&mut client);
+
+        let mut login_death_location = None;
+
+        conn.for_each_packet(|pkt| {
+            if let S2cPlayPacket::LoginPlay(p) = pkt {
+                login_death_location = Some(p.last_death_location.map(|l| l.position));
+            }
+        });
+
+        assert_eq!(login_death_location, Some(Some(BlockPos::new(1, 2, 3))));
+
+        client.clear_death_location();
+        assert_eq!(client.death_location(), None);
+
+        client.respawn(world_id);
+        client.update(1, &shared, &entities, &worlds, &player_lists, &inventories);
+        flush(&mut client);
+
+        let mut respawned = false;
+
+        conn.for_each_packet(|pkt| {
+            if let S2cPlayPacket::Respawn(p) = pkt {
+                assert!(p.last_death_location.is_none());
+                respawned = true;
+            }
+        });
+
+        assert!(respawned);
+    }
+
+    #[test]
+    fn login_and_respawn_use_same_hashed_seed() {
+        let shared = setup_server(MockConfig::default()).unwrap();
+        let _guard = shared.tokio_handle().enter();
+
+        let (mut client, mut conn) = new_client();
+        let entities = Entities::new();
+        let mut worlds = Worlds::new(shared.clone());
+        let player_lists = PlayerLists::new(None);
+        let inventories = Inventories::new();
+
+        let (world_id, world) = worlds.insert(DimensionId::default(), ());
+        world.set_terrain_seed(12345);
+        assert_eq!(world.terrain_seed(), 12345);
+        let hashed_seed = world.hashed_seed();
+
+        client.respawn(world_id);
+        client.update(0, &shared, &entities, &worlds, &player_lists, &inventories);
+        flush(&mut client);
+
+        let mut login_seed = None;
+
+        conn.for_each_packet(|pkt| {
+            if let S2cPlayPacket::LoginPlay(p) = pkt {
+                login_seed = Some(p.hashed_seed);
+            }
+        });
+
+        client.respawn(world_id);
+        client.update(1, &shared, &entities, &worlds, &player_lists, &inventories);
+        flush(&mut client);
+
+        let mut respawn_seed = None;
+
+        conn.for_each_packet(|pkt| {
+            if let S2cPlayPacket::Respawn(p) = pkt {
+                respawn_seed = Some(p.hashed_seed as i64);
+            }
+        });
+
+        assert_eq!(login_seed, Some(hashed_seed));
+        assert_eq!(respawn_seed, Some(hashed_seed));
+    }
+
+    #[tokio::test]
+    async fn dust_particle_is_encoded() {
+        let (mut client, mut conn) = new_client();
+
+        let dust = Particle::Dust {
+            rgb: Vec3::new(1.0, 0.5, 0.0),
+            scale: 2.0,
+        };
+
+        client.spawn_particle(dust, true, Vec3::new(1.0, 2.0, 3.0), Vec3::zero(), 0.0, 1);
+        flush(&mut client);
+
+        let mut spawned = false;
+
+        conn.for_each_packet(|pkt| {
+            if let S2cPlayPacket::ParticleS2c(p) = pkt {
+                assert_eq!(p.particle_id.0, 14);
+                assert!(p.long_distance);
+                assert_eq!(p.position, [1.0, 2.0, 3.0]);
+                assert_eq!(p.count, 1);
+
+                let floats: Vec<f32> = p
+                    .data
+                    .0
+                    .chunks(4)
+                    .map(|b| f32::from_be_bytes(b.try_into().unwrap()))
+                    .collect();
+                assert_eq!(floats, [1.0, 0.5, 0.0, 2.0]);
+
+                spawned = true;
+            }
+        });
+
+        assert!(spawned);
+    }
+
+    #[tokio::test]
+    async fn stop_sound_filters_and_sound_ids() {
+        let (mut client, mut conn) = new_client();
+
+        let pig = Ident::new("minecraft:entity.pig.ambient").unwrap();
+
+        client.play_sound_id(
+            42,
+            SoundCategory::Neutral,
+            Vec3::new(1.0, 2.0, 3.0),
+            1.0,
+            1.0,
+        );
+        client.stop_sound(None, None);
+        client.stop_sound(None, Some(SoundCategory::Music));
+        client.stop_sound(Some(pig), None);
+        client.stop_sound(Some(pig), Some(SoundCategory::Neutral));
+        flush(&mut client);
+
+        let mut played = false;
+        let mut filters = vec![];
+
+        conn.for_each_packet(|pkt| match pkt {
+            S2cPlayPacket::SoundEffect(p) => {
+                assert_eq!(p.id.0, 42);
+                assert_eq!(p.category, SoundCategory::Neutral);
+                assert_eq!(p.position, [8, 16, 24]);
+                played = true;
+            }
+            S2cPlayPacket::StopSound(p) => filters.push((
+                p.filter.source,
+                p.filter.sound.map(|s| s.as_str().to_owned()),
+            )),
+            _ => {}
+        });
+
+        assert!(played);
+
+        let pig = Some("minecraft:entity.pig.ambient".to_owned());
+        assert_eq!(
+            filters,
+            [
+                (None, None),
+                (Some(SoundCategory::Music), None),
+                (None, pig.clone()),
+                (Some(SoundCategory::Neutral), pig),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn empty_subtitle_is_still_sent() {
+        let (mut client, mut conn) = new_client();
+
+        client.set_title("Hello", "", None);
+        flush(&mut client);
+
+        let mut titles = vec![];
+        let mut subtitles = vec![];
+        let mut animated = false;
+
+        conn.for_each_packet(|pkt| match pkt {
+            S2cPlayPacket::SetTitleText(p) => titles.push(p.0),
+            S2cPlayPacket::SetSubtitleText(p) => subtitles.push(p.0),
+            S2cPlayPacket::SetTitleAnimationTimes(_) => animated = true,
+            _ => {}
+        });
+
+        assert_eq!(titles.len(), 1);
+        assert!(!titles[0].is_empty());
+        assert_eq!(subtitles.len(), 1);
+        assert!(subtitles[0].is_empty());
+        assert!(!animated);
+    }
+
+    #[tokio::test]
+    async fn swap_hands_action_swaps_slots_when_enabled() {
+        let (mut client, mut conn) = new_client();
+
+        let sword = ItemStack::new(ItemKind::DiamondSword, 1, None);
+        let shield = ItemStack::new(ItemKind::Shield, 1, None);
+
+        client.replace_inventory_slot(PlayerInventorySlot::Hotbar(0), sword.clone());
+        client.set_offhand(shield.clone());
+
+        let swap = PlayerAction {
+            status: DiggingStatus::SwapItemInHand,
+            position: BlockPos::new(0, 0, 0),
+            face: BlockFace::Bottom,
+            sequence: VarInt(0),
+        };
+
+        // The slots are left alone unless automatic swapping is enabled.
+        conn.send_packet(&swap);
+        client.prepare_c2s_packets();
+
+        assert!(matches!(
+            client.next_event(),
+            Some(ClientEvent::SwapItemInHand)
+        ));
+        assert_eq!(client.held_item(), Some(&sword));
+
+        client.set_auto_swap_hands(true);
+        client.modified_slots = 0;
+
+        conn.send_packet(&swap);
+        client.prepare_c2s_packets();
+
+        assert!(matches!(
+            client.next_event(),
+            Some(ClientEvent::SwapItemInHand)
+        ));
+        assert_eq!(client.held_item(), Some(&shield));
+        assert_eq!(
+            client.inventory_slot(PlayerInventorySlot::Offhand),
+            Some(&sword)
+        );
+
+        let main = PlayerInventorySlot::Hotbar(0).to_raw().unwrap();
+        let offhand = PlayerInventorySlot::Offhand.to_raw().unwrap();
+        assert_eq!(client.modified_slots, (1 << main) | (1 << offhand));
+        assert!(client.bits.held_item_modified());
+    }
+
+    #[tokio::test]
+    async fn drop_actions_generate_drop_item_events() {
+        let (mut client, mut conn) = new_client();
+
+        client.replace_slot(36, ItemStack::new(ItemKind::Stone, 5, None));
+
+        let mut press_drop = |client: &mut Client<MockConfig>, status| {
+            conn.send_packet(&PlayerAction {
+                status,
+                position: BlockPos::new(0, 0, 0),
+                face: BlockFace::Bottom,
+                sequence: VarInt(0),
+            });
+            client.prepare_c2s_packets();
+
+            match client.next_event() {
+                Some(ClientEvent::DropItem { full_stack }) => full_stack,
+                other => panic!("unexpected event: {other:?}"),
+            }
+        };
+
+        // The held item is left alone unless automatic dropping is enabled.
+        assert!(!press_drop(&mut client, DiggingStatus::DropItem));
+        assert_eq!(client.held_item().map(|i| i.count()), Some(5));
+
+        client.set_auto_drop_items(true);
+
+        assert!(!press_drop(&mut client, DiggingStatus::DropItem));
+        assert_eq!(client.held_item().map(|i| i.count()), Some(4));
+
+        assert!(press_drop(&mut client, DiggingStatus::DropItemStack));
+        assert_eq!(client.held_item(), None);
+    }
+
+    #[tokio::test]
+    async fn finish_using_item_sends_consume_status() {
+        let (mut client, mut conn) = new_client();
+
+        client.start_using_item(Hand::Off);
+        assert!(client.player().get_using_item());
+        assert!(client.player().get_off_hand_active());
+
+        client.finish_using_item();
+        assert!(!client.player().get_using_item());
+        assert!(!client.player().get_off_hand_active());
+        flush(&mut client);
+
+        let mut statuses = vec![];
+        conn.for_each_packet(|pkt| {
+            if let S2cPlayPacket::EntityEvent(p) = pkt {
+                statuses.push((p.entity_id, p.entity_status));
+            }
+        });
+
+        assert_eq!(statuses, [(0, 9)]);
+    }
+
+    #[tokio::test]
+    async fn plugin_messages_are_surfaced_unless_oversized() {
+        let (mut client, mut conn) = new_client();
+
+        let brand = Ident::new("minecraft:brand").unwrap();
+        let oversized = vec![0; MAX_PLUGIN_MESSAGE_LEN + 1];
+
+        conn.send_packet(&PluginMessageC2s {
+            channel: brand,
+            data: RawBytes(&oversized),
+        });
+        conn.send_packet(&PluginMessageC2s {
+            channel: brand,
+            data: RawBytes(b"\x07vanilla"),
+        });
+        client.prepare_c2s_packets();
+
+        match client.next_event() {
+            Some(ClientEvent::PluginMessage { channel, data }) => {
+                assert_eq!(channel, brand);
+                assert_eq!(&*data, b"\x07vanilla");
+            }
+            other => panic!("unexpected event: {other:?}"),
+        }
+
+        assert!(client.next_event().is_none());
+        assert!(!client.is_disconnected());
+    }
+
+    #[tokio::test]
+    async fn set_cooldown_sends_item_id_and_ticks() {
+        let (mut client, mut conn) = new_client();
+
+        client.set_cooldown(ItemKind::EnderPearl, 20);
+        client.set_cooldown(ItemKind::EnderPearl, 0);
+        flush(&mut client);
+
+        let mut cooldowns = vec![];
+        conn.for_each_packet(|pkt| {
+            if let S2cPlayPacket::SetCooldown(p) = pkt {
+                cooldowns.push((p.item_id.0, p.cooldown_ticks.0));
+            }
+        });
+
+        let id = ItemKind::EnderPearl.to_raw() as i32;
+        assert_eq!(cooldowns, [(id, 20), (id, 0)]);
+    }
+
+    #[tokio::test]
+    async fn hide_title_does_not_reset() {
+        let (mut client, mut conn) = new_client();
+
+        client.clear_title();
+        client.hide_title();
+        flush(&mut client);
+
+        let mut resets = vec![];
+
+        conn.for_each_packet(|pkt| {
+            if let S2cPlayPacket::ClearTitles(p) = pkt {
+                resets.push(p.reset);
+            }
+        });
+
+        assert_eq!(resets, [true, false]);
+    }
+
+    #[tokio::test]
+    async fn relative_teleport_offsets_marked_axes() {
+        let (mut client, mut conn) = new_client();
+
+        client.teleport([1.0, 2.0, 3.0], 90.0, 10.0);
+        client.teleport_relative(
+            [0.5, 64.0, 0.5],
+            45.0,
+            -5.0,
+            SyncPlayerPosLookFlags::new().with_x(true).with_y_rot(true),
+        );
+        flush(&mut client);
+
+        assert_eq!(client.position(), Vec3::new(1.5, 64.0, 0.5));
+        assert_eq!(client.yaw(), 135.0);
+        assert_eq!(client.pitch(), -5.0);
+
+        let mut teleport_ids = vec![];
+
+        conn.for_each_packet(|pkt| {
+            if let S2cPlayPacket::SynchronizePlayerPosition(p) = pkt {
+                teleport_ids.push(p.teleport_id.0);
+
+                if p.teleport_id.0 == 1 {
+                    assert!(p.flags.x() && p.flags.y_rot());
+                    assert!(!p.flags.y() && !p.flags.z() && !p.flags.x_rot());
+                    assert_eq!(p.position, [0.5, 64.0, 0.5]);
+                    assert_eq!((p.yaw, p.pitch), (45.0, -5.0));
+                }
+            }
+        });
+
+        // Relative teleports are confirmed like absolute ones.
+        assert_eq!(teleport_ids, [0, 1]);
+        assert_eq!(client.pending_teleports, 2);
+    }
+
+    #[tokio::test]
+    async fn teleport_is_absolute() {
+        let (mut client, mut conn) = new_client();
+
+        client.teleport([1.0, -64.0, 3.0], 90.0, 45.0);
+        flush(&mut client);
+
+        let mut teleported = false;
+
+        conn.for_each_packet(|pkt| {
+            if let S2cPlayPacket::SynchronizePlayerPosition(p) = pkt {
+                // Relative flags would keep the client's falling velocity.
+                assert_eq!(p.flags, SyncPlayerPosLookFlags::new());
+                assert_eq!(p.position, [1.0, -64.0, 3.0]);
+                teleported = true;
+            }
+        });
+
+        assert!(teleported);
+        assert_eq!(client.pending_teleports, 1);
     }
 }
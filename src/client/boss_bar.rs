@@ -0,0 +1,44 @@
+use valence_protocol::types::BossBarAction;
+pub use valence_protocol::types::{BossBarColor, BossBarDivision, BossBarFlags};
+use valence_protocol::Text;
+
+/// A boss bar shown at the top of a client's screen.
+///
+/// Boss bars are shown with [`Client::add_boss_bar`].
+///
+/// [`Client::add_boss_bar`]: crate::client::Client::add_boss_bar
+#[derive(Clone, PartialEq, Debug)]
+pub struct BossBar {
+    /// The text displayed above the bar.
+    pub title: Text,
+    /// How full the bar is. Clamped to `0.0..=1.0` when sent.
+    pub health: f32,
+    pub color: BossBarColor,
+    pub division: BossBarDivision,
+    /// Whether the bar darkens the sky, plays the dragon music, or creates
+    /// fog.
+    pub flags: BossBarFlags,
+}
+
+impl BossBar {
+    /// Creates a full pink boss bar with no notches and no flags set.
+    pub fn new(title: impl Into<Text>) -> Self {
+        Self {
+            title: title.into(),
+            health: 1.0,
+            color: BossBarColor::Pink,
+            division: BossBarDivision::NoDivision,
+            flags: BossBarFlags::new(),
+        }
+    }
+
+    pub(super) fn into_add_action(self) -> BossBarAction {
+        BossBarAction::Add {
+            title: self.title,
+            health: self.health.clamp(0.0, 1.0),
+            color: self.color,
+            division: self.division,
+            flags: self.flags,
+        }
+    }
+}
@@ -1,6 +1,7 @@
 use std::cmp;
 
 use anyhow::bail;
+use tracing::warn;
 use uuid::Uuid;
 use valence_protocol::entity_meta::Pose;
 use valence_protocol::packets::c2s::play::{
@@ -44,6 +45,9 @@ pub enum ClientEvent {
         timestamp: u64,
     },
     ChatPreview,
+    /// The client wants to respawn after dying. If
+    /// [automatic respawning](Client::set_auto_respawn) is enabled, the client
+    /// has already been respawned at its spawn position.
     PerformRespawn,
     RequestStats,
     UpdateSettings {
@@ -81,6 +85,11 @@ pub enum ClientEvent {
     CloseContainer {
         window_id: i8,
     },
+    /// A custom payload sent by the client, such as its brand on the
+    /// `minecraft:brand` channel or data from a mod.
+    ///
+    /// Payloads longer than [`MAX_PLUGIN_MESSAGE_LEN`] bytes are dropped
+    /// without generating an event.
     PluginMessage {
         channel: Ident<Box<str>>,
         data: Box<[u8]>,
@@ -174,10 +183,16 @@ pub enum ClientEvent {
         face: BlockFace,
         sequence: i32,
     },
-    DropItem,
-    DropItemStack,
+    /// The client pressed the key to drop the item it is holding. See
+    /// [`Client::set_auto_drop_items`].
+    DropItem {
+        /// If the whole stack was dropped instead of a single item.
+        full_stack: bool,
+    },
     /// Eating food, pulling back bows, using buckets, etc.
     UpdateHeldItemState,
+    /// The client pressed the key to swap the items in its hands. See
+    /// [`Client::set_auto_swap_hands`].
     SwapItemInHand,
     PlayerInput {
         sideways: f32,
@@ -214,6 +229,8 @@ pub enum ClientEvent {
         primary_effect: Option<i32>,
         secondary_effect: Option<i32>,
     },
+    /// The client selected a different hotbar slot. The slot is in `0..9` and
+    /// is also returned by [`Client::selected_slot`] from now on.
     SetHeldItem {
         slot: i16,
     },
@@ -284,6 +301,11 @@ pub enum ClientEvent {
     },
 }
 
+/// The maximum length of the payload in a
+/// [`ClientEvent::PluginMessage`]. This is the same limit the vanilla server
+/// uses.
+pub const MAX_PLUGIN_MESSAGE_LEN: usize = 32767;
+
 pub(super) fn next_event_fallible<C: Config>(
     client: &mut Client<C>,
 ) -> anyhow::Result<Option<ClientEvent>> {
@@ -338,7 +360,13 @@ pub(super) fn next_event_fallible<C: Config>(
             },
             C2sPlayPacket::ChatPreviewC2s(_) => ClientEvent::ChatPreview,
             C2sPlayPacket::ClientCommand(p) => match p {
-                ClientCommand::PerformRespawn => ClientEvent::PerformRespawn,
+                ClientCommand::PerformRespawn => {
+                    if client.bits.auto_respawn() {
+                        client.respawn_at_spawn_position();
+                    }
+
+                    ClientEvent::PerformRespawn
+                }
                 ClientCommand::RequestStats => ClientEvent::RequestStats,
             },
             C2sPlayPacket::ClientInformation(p) => ClientEvent::UpdateSettings {
@@ -366,6 +394,10 @@ pub(super) fn next_event_fallible<C: Config>(
                 for (idx, item) in &p.slots {
                     // TODO: check bounds on indices.
                     client.slots[*idx as usize] = item.clone();
+
+                    if *idx as u16 == client.held_item_raw_slot() {
+                        client.bits.set_held_item_modified(true);
+                    }
                 }
 
                 ClientEvent::ClickContainer {
@@ -378,13 +410,32 @@ pub(super) fn next_event_fallible<C: Config>(
                     carried_item: p.carried_item,
                 }
             }
-            C2sPlayPacket::CloseContainerC2s(p) => ClientEvent::CloseContainer {
-                window_id: p.window_id,
-            },
-            C2sPlayPacket::PluginMessageC2s(p) => ClientEvent::PluginMessage {
-                channel: p.channel.into(),
-                data: p.data.0.into(),
-            },
+            C2sPlayPacket::CloseContainerC2s(p) => {
+                if p.window_id as u8 == client.window_id {
+                    client.bits.set_window_open(false);
+                }
+
+                ClientEvent::CloseContainer {
+                    window_id: p.window_id,
+                }
+            }
+            C2sPlayPacket::PluginMessageC2s(p) => {
+                // Drop oversized payloads instead of disconnecting the client.
+                if p.data.0.len() > MAX_PLUGIN_MESSAGE_LEN {
+                    warn!(
+                        username = %client.username,
+                        "dropping {} byte plugin message on channel {}",
+                        p.data.0.len(),
+                        p.channel
+                    );
+                    continue;
+                }
+
+                ClientEvent::PluginMessage {
+                    channel: p.channel.into(),
+                    data: p.data.0.into(),
+                }
+            }
             C2sPlayPacket::EditBook(p) => ClientEvent::EditBook {
                 slot: p.slot.0,
                 entries: p.entries.into_iter().map(From::from).collect(),
@@ -535,10 +586,23 @@ pub(super) fn next_event_fallible<C: Config>(
                         face: p.face,
                         sequence: p.sequence.0,
                     },
-                    DiggingStatus::DropItemStack => ClientEvent::DropItemStack,
-                    DiggingStatus::DropItem => ClientEvent::DropItem,
+                    DiggingStatus::DropItemStack | DiggingStatus::DropItem => {
+                        let full_stack = p.status == DiggingStatus::DropItemStack;
+
+                        if client.auto_drop_items() {
+                            client.drop_held_item(full_stack);
+                        }
+
+                        ClientEvent::DropItem { full_stack }
+                    }
                     DiggingStatus::UpdateHeldItemState => ClientEvent::UpdateHeldItemState,
-                    DiggingStatus::SwapItemInHand => ClientEvent::SwapItemInHand,
+                    DiggingStatus::SwapItemInHand => {
+                        if client.auto_swap_hands() {
+                            client.swap_hands();
+                        }
+
+                        ClientEvent::SwapItemInHand
+                    }
                 }
             }
             C2sPlayPacket::PlayerInput(p) => ClientEvent::PlayerInput {
@@ -578,7 +642,19 @@ pub(super) fn next_event_fallible<C: Config>(
                 primary_effect: p.primary_effect.map(|i| i.0),
                 secondary_effect: p.secondary_effect.map(|i| i.0),
             },
-            C2sPlayPacket::SetHeldItemC2s(p) => ClientEvent::SetHeldItem { slot: p.slot },
+            C2sPlayPacket::SetHeldItemC2s(p) => {
+                // Ignore invalid slots like the vanilla server does.
+                if !(0..9).contains(&p.slot) {
+                    continue;
+                }
+
+                if p.slot as u8 != client.selected_slot {
+                    client.selected_slot = p.slot as u8;
+                    client.bits.set_held_item_modified(true);
+                }
+
+                ClientEvent::SetHeldItem { slot: p.slot }
+            }
             C2sPlayPacket::ProgramCommandBlock(p) => ClientEvent::ProgramCommandBlock {
                 position: p.position,
                 command: p.command.into(),
@@ -7,11 +7,13 @@ use async_trait::async_trait;
 use serde::Serialize;
 use tokio::runtime::Handle as TokioHandle;
 use uuid::Uuid;
+use valence_nbt::Compound;
 use valence_protocol::text::Text;
 use valence_protocol::username::Username;
 use valence_protocol::MAX_PACKET_SIZE;
 
 use crate::biome::Biome;
+use crate::client::ClientId;
 use crate::dimension::Dimension;
 use crate::server::{NewClientData, Server, SharedServer};
 use crate::{Ticks, STANDARD_TPS};
@@ -88,6 +90,86 @@ pub trait Config: Sized + Send + Sync + 'static {
         STANDARD_TPS
     }
 
+    /// Called once at startup to get the number of ticks over which
+    /// [`SharedServer::tps`] is measured.
+    ///
+    /// A larger window gives a steadier measurement which is slower to react
+    /// to lag spikes. The window must be greater than zero.
+    ///
+    /// # Default Implementation
+    ///
+    /// Returns `100`, which is five seconds at the [standard tick rate].
+    ///
+    /// [standard tick rate]: STANDARD_TPS
+    fn tps_window(&self) -> usize {
+        100
+    }
+
+    /// Called once at startup to get the number of ticks between the
+    /// keepalive packets sent to each client.
+    ///
+    /// A client which does not respond to a keepalive before the next one is
+    /// due has missed that keepalive. The interval must be greater than zero.
+    ///
+    /// # Default Implementation
+    ///
+    /// Returns ten seconds worth of ticks at the configured
+    /// [tick rate](Self::tick_rate).
+    fn keepalive_interval(&self) -> Ticks {
+        self.tick_rate() * 10
+    }
+
+    /// Called once at startup to get the number of consecutive keepalive
+    /// intervals a client may miss before it is disconnected for timing out.
+    ///
+    /// No new keepalive is sent while the client has not yet responded to the
+    /// last one, so a larger value gives clients on slow connections more
+    /// time to respond.
+    ///
+    /// # Default Implementation
+    ///
+    /// Returns `0`, which disconnects a client as soon as a single interval
+    /// passes without a response.
+    fn max_missed_keepalives(&self) -> u32 {
+        0
+    }
+
+    /// Called once at startup to determine if clients check that chunks are
+    /// never loaded twice or unloaded without being loaded first.
+    ///
+    /// These checks only exist in debug builds and panic when they fail,
+    /// which helps catch bugs in chunk loading. Servers which control the
+    /// chunks sent to clients manually may want to turn them off without
+    /// switching to a release build.
+    ///
+    /// # Default Implementation
+    ///
+    /// Returns `true`.
+    fn chunk_load_assertions(&self) -> bool {
+        true
+    }
+
+    /// Called once at startup to determine if clients are added to and removed
+    /// from player lists automatically.
+    ///
+    /// When enabled, every client is added to the player list it is viewing,
+    /// as set with
+    /// [`Client::set_player_list`](crate::client::Client::set_player_list),
+    /// using its username, skin, and game mode. The entry is removed when the
+    /// client disconnects, is removed from the server, or switches to another
+    /// player list. The game mode of the entry is kept in sync with the
+    /// client.
+    ///
+    /// Entries can still be added and modified manually, but automatically
+    /// managed entries should not be removed by hand.
+    ///
+    /// # Default Implementation
+    ///
+    /// Returns `false`.
+    fn auto_manage_player_list(&self) -> bool {
+        false
+    }
+
     /// Called to get the connection mode option, which determines if client
     /// authentication and encryption should take place and if the server
     /// should get the player data from a proxy.
@@ -243,6 +325,26 @@ pub trait Config: Sized + Send + Sync + 'static {
         vec![Biome::default()]
     }
 
+    /// Called once at startup to get the tiers used to reduce how often the
+    /// movement of distant entities is sent to clients.
+    ///
+    /// An [`EntityUpdateTier`] applies to entities in chunks at least
+    /// `min_distance` chunks away from a client along the X or Z axis. The
+    /// position and rotation of those entities are only sent every `interval`
+    /// ticks. Teleports, velocity, tracked data, equipment, and entity events
+    /// are never delayed.
+    ///
+    /// If multiple tiers apply, the one with the largest `min_distance` is
+    /// used. Every `interval` must be greater than zero.
+    ///
+    /// # Default Implementation
+    ///
+    /// Returns an empty `Vec`. The movement of all entities is sent every
+    /// tick.
+    fn entity_update_tiers(&self) -> Vec<EntityUpdateTier> {
+        vec![]
+    }
+
     /// Called when the server receives a Server List Ping query.
     /// Data for the response can be provided or the query can be ignored.
     ///
@@ -281,6 +383,23 @@ pub trait Config: Sized + Send + Sync + 'static {
         Ok(())
     }
 
+    /// Called when a client is added to the server to get the registry codec
+    /// sent in its login packet. This allows clients to be given different
+    /// registries, such as clients connecting with another game version.
+    ///
+    /// The returned codec must still contain every dimension and biome used by
+    /// the server or the client may fail to join.
+    ///
+    /// This method is called from within a tokio runtime.
+    ///
+    /// # Default Implementation
+    ///
+    /// Returns `None`, which sends the registry codec built from
+    /// [`Self::dimensions`] and [`Self::biomes`].
+    fn registry_codec_for(&self, ncd: &NewClientData) -> Option<Compound> {
+        None
+    }
+
     /// Called after the server is created, but prior to accepting connections
     /// and entering the update loop.
     ///
@@ -305,6 +424,42 @@ pub trait Config: Sized + Send + Sync + 'static {
     ///
     /// The default implementation does nothing.
     fn update(&self, server: &mut Server<Self>) {}
+
+    /// Called exactly once for every client that joins the server.
+    ///
+    /// On the tick the client joins, this is called before [`Self::update`]
+    /// and before the client has received any packets. The client can be
+    /// accessed through `server.clients[client]`.
+    ///
+    /// This method is called from within a tokio runtime.
+    ///
+    /// # Default Implementation
+    ///
+    /// The default implementation does nothing.
+    fn on_client_join(&self, server: &mut Server<Self>, client: ClientId) {}
+
+    /// Called exactly once for every client that has disconnected or was
+    /// removed, after [`Self::on_client_join`] was called for it.
+    ///
+    /// This is called at the beginning of a tick, before [`Self::update`].
+    /// Clients that disconnected are still present in `server.clients` and may
+    /// be removed here or later. Clients removed from `server.clients` before
+    /// they disconnected are passed to this method at the beginning of the
+    /// next tick, when their ID is no longer valid.
+    ///
+    /// Disconnections are observed when the clients' packets are received at
+    /// the beginning of a tick. Clients that disconnect later in the tick,
+    /// such as when the connection fails during the client update or
+    /// [`Client::disconnect`](crate::client::Client::disconnect) is called
+    /// from [`Self::update`], are passed to this method at the beginning of
+    /// the next tick.
+    ///
+    /// This method is called from within a tokio runtime.
+    ///
+    /// # Default Implementation
+    ///
+    /// The default implementation does nothing.
+    fn on_client_leave(&self, server: &mut Server<Self>, client: ClientId) {}
 }
 
 /// The result of the [`server_list_ping`](Config::server_list_ping) callback.
@@ -345,6 +500,17 @@ pub struct PlayerSampleEntry<'a> {
     pub id: Uuid,
 }
 
+/// Describes how often the movement of distant entities is sent to clients.
+/// See [`Config::entity_update_tiers`].
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct EntityUpdateTier {
+    /// The minimum distance in chunks between a client and an entity for this
+    /// tier to apply.
+    pub min_distance: u8,
+    /// The number of ticks between movement updates.
+    pub interval: Ticks,
+}
+
 /// Describes how new connections to the server are handled.
 #[non_exhaustive]
 #[derive(Clone, PartialEq, Default)]
@@ -406,6 +572,15 @@ pub(crate) struct MockConfig<S = (), Cl = (), E = (), W = (), Ch = (), P = (), I
     _marker: std::marker::PhantomData<(S, Cl, E, W, Ch, P, I)>,
 }
 
+#[cfg(test)]
+impl<S, Cl, E, W, Ch, P, I> Default for MockConfig<S, Cl, E, W, Ch, P, I> {
+    fn default() -> Self {
+        Self {
+            _marker: std::marker::PhantomData,
+        }
+    }
+}
+
 #[cfg(test)]
 impl<S, Cl, E, W, Ch, P, I> Config for MockConfig<S, Cl, E, W, Ch, P, I>
 where
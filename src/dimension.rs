@@ -13,7 +13,8 @@ use crate::LIBRARY_NAMESPACE;
 /// [configuration](crate::config::Config).
 ///
 /// To obtain dimension IDs for other dimensions, call
-/// [`dimensions`](crate::server::SharedServer::dimensions).
+/// [`dimensions`](crate::server::SharedServer::dimensions) or
+/// [`dimension_id_of`](crate::server::SharedServer::dimension_id_of).
 #[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
 pub struct DimensionId(pub(crate) u16);
 
@@ -22,7 +23,12 @@ impl DimensionId {
         ident!("{LIBRARY_NAMESPACE}:dimension_type_{}", self.0)
     }
 
-    pub(crate) fn dimension_name(self) -> Ident<String> {
+    /// Returns the name of this dimension as seen by clients. The name can be
+    /// converted back to the dimension ID with
+    /// [`SharedServer::dimension_id_of`].
+    ///
+    /// [`SharedServer::dimension_id_of`]: crate::server::SharedServer::dimension_id_of
+    pub fn dimension_name(self) -> Ident<String> {
         ident!("{LIBRARY_NAMESPACE}:dimension_{}", self.0)
     }
 }
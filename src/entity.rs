@@ -1,8 +1,9 @@
 //! Entities in a world.
 
 use std::collections::hash_map::Entry;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::iter::FusedIterator;
+use std::mem;
 use std::num::NonZeroU32;
 use std::ops::{Deref, DerefMut, Index, IndexMut, Range};
 
@@ -13,22 +14,25 @@ use uuid::Uuid;
 use valence_protocol::entity_meta::{Facing, PaintingKind, Pose};
 use valence_protocol::packets::s2c::play::{
     EntityAnimationS2c, EntityEvent as EntityEventPacket, SetEntityMetadata, SetEntityVelocity,
-    SetHeadRotation, SpawnEntity, SpawnExperienceOrb, SpawnPlayer, TeleportEntity,
-    UpdateEntityPosition, UpdateEntityPositionAndRotation, UpdateEntityRotation,
+    SetEquipment, SetHeadRotation, SetPassengers, SpawnEntity, SpawnExperienceOrb, SpawnPlayer,
+    TeleportEntity, UpdateEntityPosition, UpdateEntityPositionAndRotation, UpdateEntityRotation,
 };
-use valence_protocol::{ByteAngle, RawBytes, VarInt};
+use valence_protocol::types::{EquipmentList, EquipmentSlot};
+use valence_protocol::{ByteAngle, ItemStack, RawBytes, VarInt};
 use vek::{Aabb, Vec3};
 
 use crate::config::Config;
 use crate::packet::WritePacket;
-use crate::server::PlayPacketSender;
 use crate::slab_versioned::{Key, VersionedSlab};
-use crate::util::aabb_from_bottom_and_size;
+use crate::util::{aabb_from_bottom_and_size, from_yaw_and_pitch};
 use crate::world::WorldId;
-use crate::STANDARD_TPS;
+use crate::{Ticks, STANDARD_TPS};
 
 pub mod data;
 
+/// The speed of a newly cast fishing bobber in meters per second.
+const FISHING_BOBBER_CAST_SPEED: f32 = 12.0;
+
 include!(concat!(env!("OUT_DIR"), "/entity_event.rs"));
 
 /// A container for all [`Entity`]s on a server.
@@ -85,6 +89,8 @@ impl<C: Config> Entities<C> {
                     state,
                     variants: TrackedData::new(kind),
                     self_update_range: 0..0,
+                    self_throttled_update_range: 0..0,
+                    last_moved_tick: 0,
                     events: vec![],
                     bits: EntityBits::new(),
                     world: WorldId::NULL,
@@ -96,6 +102,10 @@ impl<C: Config> Entities<C> {
                     head_yaw: 0.0,
                     velocity: Vec3::default(),
                     uuid,
+                    owner: None,
+                    equipment: Default::default(),
+                    modified_equipment: 0,
+                    passengers: vec![],
                 });
 
                 // TODO check for overflowing version?
@@ -108,6 +118,37 @@ impl<C: Config> Entities<C> {
         }
     }
 
+    /// Spawns a [`FishingBobber`] owned by `owner`. The bobber is cast from
+    /// the owner's eye level in the direction the owner is looking.
+    ///
+    /// Returns `None` if `owner` does not exist.
+    ///
+    /// [`FishingBobber`]: EntityKind::FishingBobber
+    pub fn cast_fishing_bobber(
+        &mut self,
+        owner: EntityId,
+        state: C::EntityState,
+    ) -> Option<(EntityId, &mut Entity<C>)> {
+        let owner_entity = self.get(owner)?;
+
+        let world = owner_entity.world();
+        let eye_height = owner_entity.hitbox().size().h * 0.85;
+        let position = owner_entity.position() + Vec3::new(0.0, eye_height, 0.0);
+        let (yaw, pitch) = (owner_entity.yaw(), owner_entity.pitch());
+        let dir = from_yaw_and_pitch(yaw as f64, pitch as f64);
+
+        let (id, bobber) = self.insert(EntityKind::FishingBobber, state);
+
+        bobber.set_world(world);
+        bobber.set_position(position);
+        bobber.set_yaw(yaw);
+        bobber.set_pitch(pitch);
+        bobber.set_velocity(dir.as_::<f32>() * FISHING_BOBBER_CAST_SPEED);
+        bobber.owner = Some(owner);
+
+        Some((id, bobber))
+    }
+
     /// Returns the number of entities in this container.
     pub fn len(&self) -> usize {
         self.slab.len()
@@ -197,6 +238,64 @@ impl<C: Config> Entities<C> {
         self.slab.par_iter_mut().map(|(k, v)| (EntityId(k), v))
     }
 
+    /// Moves every passenger to the world and position of its vehicle, and
+    /// removes deleted entities from passenger lists.
+    ///
+    /// Vehicles are processed from the bottom of each stack upwards so that
+    /// passengers riding other passengers end up in the right place on the
+    /// same tick.
+    pub(crate) fn update_passengers(&mut self) {
+        let riding: HashSet<_> = self
+            .iter()
+            .flat_map(|(_, e)| e.passengers.iter().copied())
+            .collect();
+
+        let mut stack: Vec<_> = self
+            .iter()
+            .filter(|(_, e)| !e.passengers.is_empty())
+            .map(|(id, _)| id)
+            .collect();
+
+        // Vehicles that are not riding anything go last so they are popped
+        // first. Vehicles only reachable through a cycle are processed after.
+        stack.sort_by_key(|id| !riding.contains(id));
+
+        let mut visited = HashSet::new();
+
+        while let Some(vehicle_id) = stack.pop() {
+            if !visited.insert(vehicle_id) {
+                continue;
+            }
+
+            let vehicle = &mut self[vehicle_id];
+            let mut passengers = mem::take(&mut vehicle.passengers);
+            let (world, position) = (vehicle.world(), vehicle.position());
+            let count = passengers.len();
+
+            passengers.retain(|&id| match self.get_mut(id) {
+                Some(passenger) if id != vehicle_id && !passenger.deleted() => {
+                    passenger.set_world(world);
+                    passenger.set_position(position);
+
+                    if !passenger.passengers.is_empty() {
+                        stack.push(id);
+                    }
+
+                    true
+                }
+                _ => false,
+            });
+
+            let vehicle = &mut self[vehicle_id];
+
+            if passengers.len() != count {
+                vehicle.bits.set_passengers_modified(true);
+            }
+
+            vehicle.passengers = passengers;
+        }
+    }
+
     pub(crate) fn update(&mut self) {
         self.slab.retain(|k, entity| {
             if entity.deleted() {
@@ -218,6 +317,8 @@ impl<C: Config> Entities<C> {
                 entity.bits.set_yaw_or_pitch_modified(false);
                 entity.bits.set_head_yaw_modified(false);
                 entity.bits.set_velocity_modified(false);
+                entity.bits.set_passengers_modified(false);
+                entity.modified_equipment = 0;
 
                 true
             }
@@ -277,6 +378,11 @@ pub struct Entity<C: Config> {
     /// The range of bytes in the partition cell containing this entity's update
     /// packets.
     pub(crate) self_update_range: Range<usize>,
+    /// Like `self_update_range`, but for the partition cell's throttled update
+    /// packets.
+    pub(crate) self_throttled_update_range: Range<usize>,
+    /// The last tick this entity changed its position or rotation.
+    pub(crate) last_moved_tick: Ticks,
     events: Vec<EntityEvent>, // TODO: store this info in bits?
     world: WorldId,
     old_world: WorldId,
@@ -287,6 +393,13 @@ pub struct Entity<C: Config> {
     head_yaw: f32,
     velocity: Vec3<f32>,
     uuid: Uuid,
+    owner: Option<EntityId>,
+    /// The items held or worn by this entity, indexed by [`EquipmentSlot`].
+    equipment: [Option<ItemStack>; 6],
+    /// Contains a set bit for each modified slot in `equipment` this tick.
+    modified_equipment: u8,
+    /// The entities riding this entity.
+    passengers: Vec<EntityId>,
 }
 
 #[bitfield(u8)]
@@ -296,7 +409,8 @@ pub(crate) struct EntityBits {
     pub velocity_modified: bool,
     pub on_ground: bool,
     pub deleted: bool,
-    #[bits(3)]
+    pub passengers_modified: bool,
+    #[bits(2)]
     _pad: u8,
 }
 
@@ -330,6 +444,54 @@ impl<C: Config> Entity<C> {
         self.variants.kind()
     }
 
+    /// Gets the entity which owns this entity, if any.
+    pub fn owner(&self) -> Option<EntityId> {
+        self.owner
+    }
+
+    /// Sets the entity which owns this entity. For [`FishingBobber`]s, this
+    /// is the entity holding the fishing rod.
+    ///
+    /// The owner is sent to clients when the entity is spawned, so changing
+    /// it afterwards has no effect on clients which already see the entity.
+    ///
+    /// [`FishingBobber`]: EntityKind::FishingBobber
+    pub fn set_owner(&mut self, owner: Option<EntityId>) {
+        self.owner = owner;
+    }
+
+    /// If this entity is a fishing bobber, sets the entity hooked by it.
+    /// Does nothing otherwise.
+    pub fn set_fishing_bobber_hooked(&mut self, hooked: Option<EntityId>) {
+        if let TrackedData::FishingBobber(e) = &mut self.variants {
+            // Zero means no entity is hooked, so the ID is offset by one.
+            e.set_hook_entity_id(hooked.map_or(0, |id| id.to_raw() + 1));
+        }
+    }
+
+    /// If this entity is a fishing bobber, sets whether a fish is biting.
+    /// Clients will show the bobber being pulled under while this is `true`.
+    /// Does nothing otherwise.
+    pub fn set_fishing_bobber_biting(&mut self, biting: bool) {
+        if let TrackedData::FishingBobber(e) = &mut self.variants {
+            e.set_caught_fish(biting);
+        }
+    }
+
+    /// If this entity is a fishing bobber with an entity hooked by it, pulls
+    /// the hooked entity towards the bobber's owner.
+    ///
+    /// Returns `true` if an entity was pulled. The bobber is not deleted.
+    pub fn reel_fishing_bobber(&mut self) -> bool {
+        match &self.variants {
+            TrackedData::FishingBobber(e) if e.get_hook_entity_id() != 0 => {
+                self.push_event(EntityEvent::PullHookedEntity);
+                true
+            }
+            _ => false,
+        }
+    }
+
     /// Triggers an entity event for this entity.
     pub fn push_event(&mut self, event: EntityEvent) {
         self.events.push(event);
@@ -345,6 +507,11 @@ impl<C: Config> Entity<C> {
     }
 
     /// Sets the world this entity is located in.
+    ///
+    /// At the end of the tick, clients viewing the entity in its old world
+    /// will despawn it and clients in view of it in the new world will spawn
+    /// it. Setting the world to the one the entity is already in has no
+    /// effect.
     pub fn set_world(&mut self, world: WorldId) {
         self.world = world;
     }
@@ -429,6 +596,33 @@ impl<C: Config> Entity<C> {
         }
     }
 
+    /// Gets the value of the "silent" flag.
+    pub fn silent(&self) -> bool {
+        self.variants.get_silent()
+    }
+
+    /// Sets the value of the "silent" flag. When set, clients will not play
+    /// the ambient, hurt, or death sounds of this entity. This is useful when
+    /// the server controls all audio for the entity.
+    pub fn set_silent(&mut self, silent: bool) {
+        self.variants.set_silent(silent);
+    }
+
+    /// Gets the value of the "no gravity" flag.
+    pub fn no_gravity(&self) -> bool {
+        self.variants.get_no_gravity()
+    }
+
+    /// Sets the value of the "no gravity" flag. When set, clients will not
+    /// apply gravity to this entity, which keeps decorative entities such as
+    /// floating items and holograms in place.
+    ///
+    /// Velocity set with [`Self::set_velocity`] is still sent to clients, but
+    /// it will not be affected by gravity.
+    pub fn set_no_gravity(&mut self, no_gravity: bool) {
+        self.variants.set_no_gravity(no_gravity);
+    }
+
     /// Gets the value of the "on ground" flag.
     pub fn on_ground(&self) -> bool {
         self.bits.on_ground()
@@ -444,6 +638,99 @@ impl<C: Config> Entity<C> {
         self.uuid
     }
 
+    /// Gets the item this entity is holding or wearing in the given slot.
+    pub fn equipment(&self, slot: EquipmentSlot) -> Option<&ItemStack> {
+        self.equipment[slot.to_raw() as usize].as_ref()
+    }
+
+    /// Sets the item this entity is holding or wearing in the given slot. The
+    /// previous item in the slot is returned.
+    ///
+    /// Equipment is only visible on living entities such as players, mobs and
+    /// armor stands.
+    pub fn set_equipment(
+        &mut self,
+        slot: EquipmentSlot,
+        item: impl Into<Option<ItemStack>>,
+    ) -> Option<ItemStack> {
+        let new = item.into();
+        let old = &mut self.equipment[slot.to_raw() as usize];
+
+        if new != *old {
+            self.modified_equipment |= 1 << slot.to_raw();
+        }
+
+        mem::replace(old, new)
+    }
+
+    /// Gets the entities riding this entity.
+    pub fn passengers(&self) -> &[EntityId] {
+        &self.passengers
+    }
+
+    /// Sets the entities riding this entity, replacing any previous
+    /// passengers.
+    ///
+    /// At the end of every tick, passengers are moved to the world and
+    /// position of their vehicle. Whenever the vehicle is teleported on
+    /// clients, such as after moving a long distance, the passengers are sent
+    /// again so they stay mounted. Deleted passengers are removed
+    /// automatically.
+    ///
+    /// **Note**: clients see their own player entity with a different ID, so
+    /// a client will not see itself riding a vehicle even though other clients
+    /// do.
+    pub fn set_passengers(&mut self, passengers: impl Into<Vec<EntityId>>) {
+        let passengers = passengers.into();
+
+        if self.passengers != passengers {
+            self.passengers = passengers;
+            self.bits.set_passengers_modified(true);
+        }
+    }
+
+    /// Removes every passenger from this entity. The previous passengers are
+    /// returned.
+    pub fn dismount_passengers(&mut self) -> Vec<EntityId> {
+        if !self.passengers.is_empty() {
+            self.bits.set_passengers_modified(true);
+        }
+
+        mem::take(&mut self.passengers)
+    }
+
+    /// Writes the packet listing the passengers of this entity. This is
+    /// written separately from the init packets so that it is sent after the
+    /// passengers are spawned.
+    pub(crate) fn write_passengers_packet(
+        &self,
+        mut writer: impl WritePacket,
+        this_id: EntityId,
+    ) -> anyhow::Result<()> {
+        writer.write_packet(&SetPassengers {
+            entity_id: VarInt(this_id.to_raw()),
+            passengers: self
+                .passengers
+                .iter()
+                .map(|id| VarInt(id.to_raw()))
+                .collect(),
+        })
+    }
+
+    /// Collects the equipment slots selected by `mask` into a list for the
+    /// equipment packet. Returns `None` if no slot in the mask is occupied or
+    /// modified.
+    fn equipment_list(&self, mask: u8, include_empty: bool) -> Option<EquipmentList> {
+        let list: Vec<_> = EquipmentSlot::ALL
+            .into_iter()
+            .filter(|slot| mask & (1 << slot.to_raw()) != 0)
+            .map(|slot| (slot, self.equipment(slot).cloned()))
+            .filter(|(_, item)| include_empty || item.is_some())
+            .collect();
+
+        (!list.is_empty()).then_some(EquipmentList(list))
+    }
+
     /// Returns the hitbox of this entity.
     ///
     /// The hitbox describes the space that an entity occupies. Clients interact
@@ -746,7 +1033,7 @@ impl<C: Config> Entity<C> {
     /// the entity and initialize tracked data.
     pub(crate) fn send_init_packets(
         &self,
-        send: &mut PlayPacketSender,
+        send: &mut impl WritePacket,
         position: Vec3<f64>,
         this_id: EntityId,
         scratch: &mut Vec<u8>,
@@ -765,13 +1052,13 @@ impl<C: Config> Entity<C> {
 
         match &self.variants {
             TrackedData::Marker(_) => {}
-            TrackedData::ExperienceOrb(_) => send.append_packet(&SpawnExperienceOrb {
+            TrackedData::ExperienceOrb(_) => send.write_packet(&SpawnExperienceOrb {
                 entity_id: VarInt(this_id.to_raw()),
                 position: position.into_array(),
                 count: 0, // TODO
             })?,
             TrackedData::Player(_) => {
-                send.append_packet(&SpawnPlayer {
+                send.write_packet(&SpawnPlayer {
                     entity_id: VarInt(this_id.to_raw()),
                     player_uuid: self.uuid,
                     position: position.into_array(),
@@ -780,17 +1067,17 @@ impl<C: Config> Entity<C> {
                 })?;
 
                 // Player spawn packet doesn't include head yaw for some reason.
-                send.append_packet(&SetHeadRotation {
+                send.write_packet(&SetHeadRotation {
                     entity_id: VarInt(this_id.to_raw()),
                     head_yaw: ByteAngle::from_degrees(self.head_yaw),
                 })?;
             }
-            TrackedData::ItemFrame(e) => send.append_packet(&with_object_data(e.get_rotation()))?,
+            TrackedData::ItemFrame(e) => send.write_packet(&with_object_data(e.get_rotation()))?,
             TrackedData::GlowItemFrame(e) => {
-                send.append_packet(&with_object_data(e.get_rotation()))?
+                send.write_packet(&with_object_data(e.get_rotation()))?
             }
 
-            TrackedData::Painting(_) => send.append_packet(&with_object_data(
+            TrackedData::Painting(_) => send.write_packet(&with_object_data(
                 match ((self.yaw + 45.0).rem_euclid(360.0) / 90.0) as u8 {
                     0 => 3,
                     1 => 4,
@@ -799,28 +1086,42 @@ impl<C: Config> Entity<C> {
                 },
             ))?,
             // TODO: set block state ID for falling block.
-            TrackedData::FallingBlock(_) => send.append_packet(&with_object_data(1))?,
-            TrackedData::FishingBobber(e) => {
-                send.append_packet(&with_object_data(e.get_hook_entity_id()))?
-            }
+            TrackedData::FallingBlock(_) => send.write_packet(&with_object_data(1))?,
+            TrackedData::FishingBobber(_) => send.write_packet(&with_object_data(
+                self.owner.map_or(0, |owner| owner.to_raw()),
+            ))?,
             TrackedData::Warden(e) => {
-                send.append_packet(&with_object_data((e.get_pose() == Pose::Emerging).into()))?
+                send.write_packet(&with_object_data((e.get_pose() == Pose::Emerging).into()))?
             }
-            _ => send.append_packet(&with_object_data(0))?,
+            _ => send.write_packet(&with_object_data(0))?,
         }
 
         scratch.clear();
         self.variants.write_initial_tracked_data(scratch);
         if !scratch.is_empty() {
-            send.append_packet(&SetEntityMetadata {
+            send.write_packet(&SetEntityMetadata {
                 entity_id: VarInt(this_id.to_raw()),
                 metadata: RawBytes(scratch),
             })?;
         }
 
+        if let Some(equipment) = self.equipment_list(u8::MAX, false) {
+            send.write_packet(&SetEquipment {
+                entity_id: VarInt(this_id.to_raw()),
+                equipment,
+            })?;
+        }
+
         Ok(())
     }
 
+    /// If this entity changed its position or rotation this tick.
+    pub(crate) fn moved(&self) -> bool {
+        self.position != self.old_position
+            || self.bits.yaw_or_pitch_modified()
+            || self.bits.head_yaw_modified()
+    }
+
     /// Writes the appropriate packets to update the entity (Position, tracked
     /// data, and event packets).
     pub(crate) fn write_update_packets(
@@ -828,6 +1129,61 @@ impl<C: Config> Entity<C> {
         mut writer: impl WritePacket,
         this_id: EntityId,
         scratch: &mut Vec<u8>,
+    ) -> anyhow::Result<()> {
+        self.write_movement_packets(&mut writer, this_id, true)?;
+        self.write_state_packets(writer, this_id, scratch)
+    }
+
+    /// Like [`Self::write_update_packets`], but relative movement and rotation
+    /// packets are left out. Used when the movement of this entity is sent to
+    /// a client at a reduced rate.
+    pub(crate) fn write_throttled_update_packets(
+        &self,
+        mut writer: impl WritePacket,
+        this_id: EntityId,
+        scratch: &mut Vec<u8>,
+    ) -> anyhow::Result<()> {
+        self.write_movement_packets(&mut writer, this_id, false)?;
+        self.write_state_packets(writer, this_id, scratch)
+    }
+
+    /// Writes the packets to set the absolute position and rotation of this
+    /// entity on the client.
+    pub(crate) fn write_sync_packets(
+        &self,
+        mut writer: impl WritePacket,
+        this_id: EntityId,
+        position: Vec3<f64>,
+    ) -> anyhow::Result<()> {
+        let entity_id = VarInt(this_id.to_raw());
+
+        writer.write_packet(&TeleportEntity {
+            entity_id,
+            position: position.into_array(),
+            yaw: ByteAngle::from_degrees(self.yaw),
+            pitch: ByteAngle::from_degrees(self.pitch),
+            on_ground: self.bits.on_ground(),
+        })?;
+
+        writer.write_packet(&SetHeadRotation {
+            entity_id,
+            head_yaw: ByteAngle::from_degrees(self.head_yaw),
+        })?;
+
+        if !self.passengers.is_empty() {
+            self.write_passengers_packet(writer, this_id)?;
+        }
+
+        Ok(())
+    }
+
+    /// Writes the position, rotation, and velocity packets. If `relative` is
+    /// false, only teleports and velocity are written.
+    fn write_movement_packets(
+        &self,
+        mut writer: impl WritePacket,
+        this_id: EntityId,
+        relative: bool,
     ) -> anyhow::Result<()> {
         let entity_id = VarInt(this_id.to_raw());
 
@@ -835,30 +1191,32 @@ impl<C: Config> Entity<C> {
         let needs_teleport = position_delta.map(f64::abs).reduce_partial_max() >= 8.0;
         let changed_position = self.position != self.old_position;
 
-        if changed_position && !needs_teleport && self.bits.yaw_or_pitch_modified() {
-            writer.write_packet(&UpdateEntityPositionAndRotation {
-                entity_id,
-                delta: (position_delta * 4096.0).as_::<i16>().into_array(),
-                yaw: ByteAngle::from_degrees(self.yaw),
-                pitch: ByteAngle::from_degrees(self.pitch),
-                on_ground: self.bits.on_ground(),
-            })?;
-        } else {
-            if changed_position && !needs_teleport {
-                writer.write_packet(&UpdateEntityPosition {
+        if relative {
+            if changed_position && !needs_teleport && self.bits.yaw_or_pitch_modified() {
+                writer.write_packet(&UpdateEntityPositionAndRotation {
                     entity_id,
                     delta: (position_delta * 4096.0).as_::<i16>().into_array(),
-                    on_ground: self.bits.on_ground(),
-                })?;
-            }
-
-            if self.bits.yaw_or_pitch_modified() {
-                writer.write_packet(&UpdateEntityRotation {
-                    entity_id,
                     yaw: ByteAngle::from_degrees(self.yaw),
                     pitch: ByteAngle::from_degrees(self.pitch),
                     on_ground: self.bits.on_ground(),
                 })?;
+            } else {
+                if changed_position && !needs_teleport {
+                    writer.write_packet(&UpdateEntityPosition {
+                        entity_id,
+                        delta: (position_delta * 4096.0).as_::<i16>().into_array(),
+                        on_ground: self.bits.on_ground(),
+                    })?;
+                }
+
+                if self.bits.yaw_or_pitch_modified() {
+                    writer.write_packet(&UpdateEntityRotation {
+                        entity_id,
+                        yaw: ByteAngle::from_degrees(self.yaw),
+                        pitch: ByteAngle::from_degrees(self.pitch),
+                        on_ground: self.bits.on_ground(),
+                    })?;
+                }
             }
         }
 
@@ -870,6 +1228,12 @@ impl<C: Config> Entity<C> {
                 pitch: ByteAngle::from_degrees(self.pitch),
                 on_ground: self.bits.on_ground(),
             })?;
+
+            // Keep the passengers mounted after the teleport. Modified
+            // passengers are written with the rest of the state instead.
+            if !self.passengers.is_empty() && !self.bits.passengers_modified() {
+                self.write_passengers_packet(&mut writer, this_id)?;
+            }
         }
 
         if self.bits.velocity_modified() {
@@ -879,13 +1243,25 @@ impl<C: Config> Entity<C> {
             })?;
         }
 
-        if self.bits.head_yaw_modified() {
+        if relative && self.bits.head_yaw_modified() {
             writer.write_packet(&SetHeadRotation {
                 entity_id,
                 head_yaw: ByteAngle::from_degrees(self.head_yaw),
             })?;
         }
 
+        Ok(())
+    }
+
+    /// Writes the tracked data, equipment, and event packets.
+    fn write_state_packets(
+        &self,
+        mut writer: impl WritePacket,
+        this_id: EntityId,
+        scratch: &mut Vec<u8>,
+    ) -> anyhow::Result<()> {
+        let entity_id = VarInt(this_id.to_raw());
+
         scratch.clear();
         self.variants.write_updated_tracked_data(scratch);
         if !scratch.is_empty() {
@@ -895,6 +1271,17 @@ impl<C: Config> Entity<C> {
             })?;
         }
 
+        if let Some(equipment) = self.equipment_list(self.modified_equipment, true) {
+            writer.write_packet(&SetEquipment {
+                entity_id,
+                equipment,
+            })?;
+        }
+
+        if self.bits.passengers_modified() {
+            self.write_passengers_packet(&mut writer, this_id)?;
+        }
+
         for &event in &self.events {
             match event.status_or_animation() {
                 StatusOrAnimation::Status(code) => writer.write_packet(&EntityEventPacket {
@@ -919,7 +1306,11 @@ pub(crate) fn velocity_to_packet_units(vel: Vec3<f32>) -> Vec3<i16> {
 
 #[cfg(test)]
 mod tests {
+    use valence_protocol::packets::S2cPlayPacket;
+    use valence_protocol::Decode;
+
     use super::*;
+    use crate::packet::{decode_packets, PacketWriter};
 
     type MockConfig = crate::config::MockConfig<(), (), u8>;
 
@@ -996,4 +1387,161 @@ mod tests {
         assert_eq!(maybe_cat.state, 75);
         assert_eq!(entities.len(), 2);
     }
+
+    #[test]
+    fn fishing_bobber_cast_and_bite() {
+        let mut entities: Entities<MockConfig> = Entities::new();
+        let (owner, _) = entities.insert(EntityKind::Player, 1);
+        let (bobber, _) = entities.cast_fishing_bobber(owner, 2).unwrap();
+
+        // The spawn packet links the bobber to its owner.
+        let mut bytes = vec![];
+        let mut scratch = vec![];
+        let mut compression_scratch = vec![];
+        let e = &entities[bobber];
+        e.send_init_packets(
+            &mut PacketWriter::new(&mut bytes, None, &mut compression_scratch),
+            e.position(),
+            bobber,
+            &mut scratch,
+        )
+        .unwrap();
+
+        let mut r = bytes.as_slice();
+        VarInt::decode(&mut r).unwrap();
+        match S2cPlayPacket::decode(&mut r).unwrap() {
+            S2cPlayPacket::SpawnEntity(pkt) => {
+                assert_eq!(pkt.kind.0, EntityKind::FishingBobber as i32);
+                assert_eq!(pkt.data.0, owner.to_raw());
+                assert!(pkt.position[1] > 0.0);
+            }
+            pkt => panic!("unexpected packet {pkt:?}"),
+        }
+
+        let e = &mut entities[bobber];
+        e.set_fishing_bobber_biting(true);
+        e.data().write_updated_tracked_data(&mut scratch);
+        // Index 9 is the "caught fish" field of type boolean (7).
+        assert_eq!(scratch, [9, 7, 1, 0xff]);
+
+        assert!(!e.reel_fishing_bobber());
+        e.set_fishing_bobber_hooked(Some(owner));
+        assert!(e.reel_fishing_bobber());
+
+        bytes.clear();
+        e.write_state_packets(
+            PacketWriter::new(&mut bytes, None, &mut compression_scratch),
+            bobber,
+            &mut scratch,
+        )
+        .unwrap();
+
+        let mut found_event = false;
+        for pkt in decode_packets(&bytes) {
+            if let S2cPlayPacket::EntityEvent(pkt) = pkt {
+                assert_eq!(pkt.entity_id, bobber.to_raw());
+                assert_eq!(pkt.entity_status, 31);
+                found_event = true;
+            }
+        }
+        assert!(found_event);
+    }
+
+    #[test]
+    fn teleported_vehicle_keeps_passengers() {
+        let mut entities: Entities<MockConfig> = Entities::new();
+        let (boat, _) = entities.insert(EntityKind::Boat, 1);
+        let (rider, _) = entities.insert(EntityKind::Zombie, 2);
+
+        entities[boat].set_passengers(vec![rider]);
+        entities.update_passengers();
+        entities.update();
+
+        // Far enough away to be teleported on clients.
+        entities[boat].set_position([100.0, 64.0, -20.0]);
+        entities.update_passengers();
+
+        assert_eq!(entities[rider].position(), Vec3::new(100.0, 64.0, -20.0));
+
+        let mut bytes = vec![];
+        let mut scratch = vec![];
+        let mut compression_scratch = vec![];
+        entities[boat]
+            .write_update_packets(
+                PacketWriter::new(&mut bytes, None, &mut compression_scratch),
+                boat,
+                &mut scratch,
+            )
+            .unwrap();
+
+        let mut packets = vec![];
+        for pkt in decode_packets(&bytes) {
+            match pkt {
+                S2cPlayPacket::TeleportEntity(pkt) => {
+                    assert_eq!(pkt.entity_id.0, boat.to_raw());
+                    assert_eq!(pkt.position, [100.0, 64.0, -20.0]);
+                    packets.push("teleport");
+                }
+                S2cPlayPacket::SetPassengers(pkt) => {
+                    assert_eq!(pkt.entity_id.0, boat.to_raw());
+                    assert_eq!(pkt.passengers, [VarInt(rider.to_raw())]);
+                    packets.push("passengers");
+                }
+                _ => {}
+            }
+        }
+        assert_eq!(packets, ["teleport", "passengers"]);
+
+        // Deleted passengers are dismounted.
+        entities[rider].set_deleted(true);
+        entities.update_passengers();
+        assert!(entities[boat].passengers().is_empty());
+    }
+
+    #[test]
+    fn stacked_passengers_follow_bottom_vehicle() {
+        let mut entities: Entities<MockConfig> = Entities::new();
+        // Inserted top-down so the bottom vehicle is not visited first.
+        let (top, _) = entities.insert(EntityKind::Chicken, 1);
+        let (middle, _) = entities.insert(EntityKind::Zombie, 2);
+        let (bottom, _) = entities.insert(EntityKind::Boat, 3);
+
+        entities[middle].set_passengers(vec![top]);
+        entities[bottom].set_passengers(vec![middle]);
+        entities[bottom].set_position([10.0, 64.0, 10.0]);
+        entities.update_passengers();
+
+        assert_eq!(entities[middle].position(), Vec3::new(10.0, 64.0, 10.0));
+        assert_eq!(entities[top].position(), Vec3::new(10.0, 64.0, 10.0));
+    }
+
+    #[test]
+    fn silent_sets_tracked_data() {
+        let mut entities: Entities<MockConfig> = Entities::new();
+        let (_, entity) = entities.insert(EntityKind::Zombie, 1);
+        assert!(!entity.silent());
+
+        entity.set_silent(true);
+        assert!(entity.silent());
+
+        let mut buf = vec![];
+        entity.data().write_updated_tracked_data(&mut buf);
+        // Index 4 is the "silent" field of type boolean (7).
+        assert_eq!(buf, [4, 7, 1, 0xff]);
+    }
+
+    #[test]
+    fn no_gravity_sets_tracked_data() {
+        let mut entities: Entities<MockConfig> = Entities::new();
+        let (_, entity) = entities.insert(EntityKind::ArmorStand, 1);
+        assert!(!entity.no_gravity());
+
+        entity.set_no_gravity(true);
+        assert!(entity.no_gravity());
+
+        let mut buf = vec![];
+        entity.data().write_updated_tracked_data(&mut buf);
+        // Index 5 is the "no gravity" field of type boolean (7).
+        assert_eq!(buf, [5, 7, 1, 0xff]);
+    }
 }
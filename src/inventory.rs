@@ -33,6 +33,7 @@ impl<C: Config> Inventories<C> {
             kind,
             slots: vec![None; kind.slot_count()].into(),
             modified: 0,
+            on_change: None,
         });
 
         (InventoryId(id), inv)
@@ -91,6 +92,14 @@ impl InventoryId {
     pub const NULL: Self = Self(Key::NULL);
 }
 
+/// A callback invoked when a slot in an [`Inventory`] changes. See
+/// [`Inventory::set_change_callback`].
+pub type InventoryChangeCallback<C> = Box<
+    dyn FnMut(&mut <C as Config>::InventoryState, u16, Option<&ItemStack>, Option<&ItemStack>)
+        + Send
+        + Sync,
+>;
+
 pub struct Inventory<C: Config> {
     /// Custom state
     pub state: C::InventoryState,
@@ -99,6 +108,7 @@ pub struct Inventory<C: Config> {
     slots: Box<[Option<ItemStack>]>,
     /// Contains a set bit for each modified slot in `slots`.
     modified: u64,
+    on_change: Option<InventoryChangeCallback<C>>,
 }
 
 impl<C: Config> Deref for Inventory<C> {
@@ -131,13 +141,21 @@ impl<C: Config> Inventory<C> {
         assert!(idx < self.slot_count(), "slot index out of range");
 
         let new = item.into();
-        let old = &mut self.slots[idx as usize];
+        let slot = &mut self.slots[idx as usize];
 
-        if new != *old {
-            self.modified |= 1 << idx;
+        if new == *slot {
+            return mem::replace(slot, new);
         }
 
-        mem::replace(old, new)
+        self.modified |= 1 << idx;
+
+        let old = mem::replace(slot, new);
+
+        if let Some(f) = &mut self.on_change {
+            f(&mut self.state, idx, old.as_ref(), slot.as_ref());
+        }
+
+        old
     }
 
     pub fn swap_slot(&mut self, idx_a: u16, idx_b: u16) {
@@ -153,6 +171,31 @@ impl<C: Config> Inventory<C> {
         self.modified |= 1 << idx_b;
 
         self.slots.swap(idx_a as usize, idx_b as usize);
+
+        if let Some(f) = &mut self.on_change {
+            let a = self.slots[idx_a as usize].as_ref();
+            let b = self.slots[idx_b as usize].as_ref();
+
+            f(&mut self.state, idx_a, b, a);
+            f(&mut self.state, idx_b, a, b);
+        }
+    }
+
+    /// Sets the callback invoked whenever a slot in this inventory changes.
+    /// `None` removes the current callback.
+    ///
+    /// The callback is given the inventory's state, the index of the slot
+    /// that changed, and the old and new items in that slot, in that order.
+    /// It is called by [`replace_slot`](Self::replace_slot) and
+    /// [`swap_slot`](Self::swap_slot) after the slot is updated, but not when
+    /// a slot is replaced with an identical item.
+    ///
+    /// Clicks made by clients are not applied to inventories automatically,
+    /// so the callback runs once the server applies them with the methods
+    /// above. This is useful for containers shared between several clients,
+    /// such as a crafting table computing its result slot.
+    pub fn set_change_callback(&mut self, callback: Option<InventoryChangeCallback<C>>) {
+        self.on_change = callback;
     }
 
     pub fn slot_count(&self) -> u16 {
@@ -210,3 +253,161 @@ impl<C: Config> Inventory<C> {
         Ok(())
     }
 }
+
+/// A slot in a client's own inventory.
+///
+/// This is a typed alternative to the raw slot indices accepted by
+/// [`Client::slot`] and [`Client::replace_slot`]. Use [`Self::to_raw`] and
+/// [`Self::from_raw`] to convert between the two. Slots with an index out of
+/// range have no raw index.
+///
+/// The raw layout of the player inventory is as follows:
+///
+/// | Raw index | Slot                                     |
+/// |-----------|------------------------------------------|
+/// | 0         | [`CraftingResult`](Self::CraftingResult) |
+/// | 1..=4     | [`CraftingInput`](Self::CraftingInput)   |
+/// | 5         | [`Helmet`](Self::Helmet)                 |
+/// | 6         | [`Chestplate`](Self::Chestplate)         |
+/// | 7         | [`Leggings`](Self::Leggings)             |
+/// | 8         | [`Boots`](Self::Boots)                   |
+/// | 9..=35    | [`Main`](Self::Main)                     |
+/// | 36..=44   | [`Hotbar`](Self::Hotbar)                 |
+/// | 45        | [`Offhand`](Self::Offhand)               |
+///
+/// [`Client::slot`]: crate::client::Client::slot
+/// [`Client::replace_slot`]: crate::client::Client::replace_slot
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+pub enum PlayerInventorySlot {
+    /// The output slot of the 2x2 crafting grid.
+    CraftingResult,
+    /// An input slot of the 2x2 crafting grid in `0..4`, ordered left to right
+    /// and top to bottom.
+    CraftingInput(u8),
+    Helmet,
+    Chestplate,
+    Leggings,
+    Boots,
+    /// A slot in the 9x3 main inventory grid in `0..27`, ordered left to right
+    /// and top to bottom.
+    Main(u8),
+    /// A slot in the hotbar in `0..9`, ordered left to right.
+    Hotbar(u8),
+    Offhand,
+}
+
+impl PlayerInventorySlot {
+    /// The number of slots in a client's own inventory. This includes the
+    /// offhand, which the vanilla client places after the hotbar at raw index
+    /// 45.
+    pub const COUNT: u16 = 46;
+
+    /// Converts this slot into its raw slot index. Returns `None` if the index
+    /// contained in [`Self::CraftingInput`], [`Self::Main`], or
+    /// [`Self::Hotbar`] is out of range.
+    pub fn to_raw(self) -> Option<u16> {
+        Some(match self {
+            Self::CraftingResult => 0,
+            Self::CraftingInput(i @ 0..=3) => 1 + i as u16,
+            Self::Helmet => 5,
+            Self::Chestplate => 6,
+            Self::Leggings => 7,
+            Self::Boots => 8,
+            Self::Main(i @ 0..=26) => 9 + i as u16,
+            Self::Hotbar(i @ 0..=8) => 36 + i as u16,
+            Self::Offhand => 45,
+            _ => return None,
+        })
+    }
+
+    /// Converts a raw slot index into a slot. Returns `None` if the index is
+    /// out of range.
+    pub fn from_raw(idx: u16) -> Option<Self> {
+        Some(match idx {
+            0 => Self::CraftingResult,
+            1..=4 => Self::CraftingInput((idx - 1) as u8),
+            5 => Self::Helmet,
+            6 => Self::Chestplate,
+            7 => Self::Leggings,
+            8 => Self::Boots,
+            9..=35 => Self::Main((idx - 9) as u8),
+            36..=44 => Self::Hotbar((idx - 36) as u8),
+            45 => Self::Offhand,
+            _ => return None,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use valence_protocol::ItemKind;
+
+    use super::*;
+    use crate::config::MockConfig;
+
+    type Changes = Vec<(u16, Option<ItemStack>, Option<ItemStack>)>;
+
+    #[test]
+    fn change_callback_receives_old_and_new_items() {
+        let mut inventories = Inventories::<MockConfig<(), (), (), (), (), (), Changes>>::new();
+        let (_, inv) = inventories.insert(InventoryKind::Generic9x1, "Chest", vec![]);
+
+        inv.set_change_callback(Some(Box::new(|changes, idx, old, new| {
+            changes.push((idx, old.cloned(), new.cloned()));
+        })));
+
+        let stone = ItemStack::new(ItemKind::Stone, 1, None);
+        let dirt = ItemStack::new(ItemKind::Dirt, 2, None);
+
+        inv.replace_slot(3, stone.clone());
+        inv.replace_slot(3, stone.clone());
+        inv.replace_slot(3, dirt.clone());
+        inv.swap_slot(3, 5);
+
+        assert_eq!(
+            inv.state,
+            [
+                (3, None, Some(stone.clone())),
+                (3, Some(stone), Some(dirt.clone())),
+                (3, Some(dirt.clone()), None),
+                (5, None, Some(dirt)),
+            ]
+        );
+    }
+
+    #[test]
+    fn player_inventory_slot_raw_indices() {
+        assert_eq!(PlayerInventorySlot::CraftingResult.to_raw(), Some(0));
+        assert_eq!(PlayerInventorySlot::CraftingInput(0).to_raw(), Some(1));
+        assert_eq!(PlayerInventorySlot::CraftingInput(3).to_raw(), Some(4));
+        assert_eq!(PlayerInventorySlot::Helmet.to_raw(), Some(5));
+        assert_eq!(PlayerInventorySlot::Chestplate.to_raw(), Some(6));
+        assert_eq!(PlayerInventorySlot::Leggings.to_raw(), Some(7));
+        assert_eq!(PlayerInventorySlot::Boots.to_raw(), Some(8));
+        assert_eq!(PlayerInventorySlot::Main(0).to_raw(), Some(9));
+        assert_eq!(PlayerInventorySlot::Main(26).to_raw(), Some(35));
+        assert_eq!(PlayerInventorySlot::Hotbar(0).to_raw(), Some(36));
+        assert_eq!(PlayerInventorySlot::Hotbar(8).to_raw(), Some(44));
+        assert_eq!(PlayerInventorySlot::Offhand.to_raw(), Some(45));
+    }
+
+    #[test]
+    fn player_inventory_slot_round_trip() {
+        for idx in 0..PlayerInventorySlot::COUNT {
+            let slot = PlayerInventorySlot::from_raw(idx).expect("index should be in range");
+            assert_eq!(slot.to_raw(), Some(idx));
+        }
+
+        assert_eq!(
+            PlayerInventorySlot::from_raw(PlayerInventorySlot::COUNT),
+            None
+        );
+    }
+
+    #[test]
+    fn player_inventory_slot_out_of_range() {
+        assert_eq!(PlayerInventorySlot::CraftingInput(4).to_raw(), None);
+        assert_eq!(PlayerInventorySlot::Main(27).to_raw(), None);
+        assert_eq!(PlayerInventorySlot::Hotbar(9).to_raw(), None);
+    }
+}
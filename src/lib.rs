@@ -94,6 +94,9 @@
 ///
 /// [async_trait]: https://docs.rs/async-trait/latest/async_trait/
 pub use async_trait::async_trait;
+#[cfg(feature = "bench")]
+#[doc(hidden)]
+pub use chunk::entity_partition::bench;
 #[doc(inline)]
 pub use server::start_server;
 pub use valence_protocol as protocol;
@@ -108,6 +111,9 @@ pub mod dimension;
 pub mod entity;
 pub mod inventory;
 mod packet;
+#[cfg(feature = "packet_capture")]
+pub mod packet_capture;
+pub mod particle;
 pub mod player_list;
 pub mod player_textures;
 pub mod server;
@@ -122,11 +128,12 @@ pub mod world;
 pub mod prelude {
     pub use biome::{Biome, BiomeId};
     pub use chunk::{Chunk, ChunkPos, Chunks, LoadedChunk, UnloadedChunk};
-    pub use client::{Client, ClientEvent, ClientId, Clients};
-    pub use config::{Config, ConnectionMode, PlayerSampleEntry, ServerListPing};
+    pub use client::{BossBar, Client, ClientEvent, ClientId, Clients};
+    pub use config::{Config, ConnectionMode, EntityUpdateTier, PlayerSampleEntry, ServerListPing};
     pub use dimension::{Dimension, DimensionId};
     pub use entity::{Entities, Entity, EntityEvent, EntityId, EntityKind, TrackedData};
-    pub use inventory::{Inventories, Inventory, InventoryId};
+    pub use inventory::{Inventories, Inventory, InventoryId, PlayerInventorySlot};
+    pub use particle::Particle;
     pub use player_list::{PlayerList, PlayerListEntry, PlayerListId, PlayerLists};
     pub use server::{NewClientData, Server, SharedServer, ShutdownResult};
     pub use util::{from_yaw_and_pitch, to_yaw_and_pitch};
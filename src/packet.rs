@@ -1,6 +1,10 @@
 use std::io::Write;
 
+#[cfg(test)]
+use valence_protocol::packets::S2cPlayPacket;
 use valence_protocol::{write_packet, write_packet_compressed, Encode, Packet};
+#[cfg(test)]
+use valence_protocol::{Decode, VarInt};
 
 pub trait WritePacket {
     fn write_packet<P>(&mut self, packet: &P) -> anyhow::Result<()>
@@ -55,3 +59,20 @@ impl<W: Write> WritePacket for PacketWriter<'_, W> {
         Ok(self.writer.write_all(bytes)?)
     }
 }
+
+/// Decodes the uncompressed play packets in `bytes` in the order they were
+/// written. Used for testing.
+#[cfg(test)]
+pub(crate) fn decode_packets(mut bytes: &[u8]) -> Vec<S2cPlayPacket> {
+    let mut packets = vec![];
+
+    while !bytes.is_empty() {
+        let len = VarInt::decode(&mut bytes).unwrap().0 as usize;
+        let (mut pkt, rest) = bytes.split_at(len);
+        bytes = rest;
+
+        packets.push(S2cPlayPacket::decode(&mut pkt).unwrap());
+    }
+
+    packets
+}
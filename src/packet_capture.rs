@@ -0,0 +1,190 @@
+//! Recording the packets sent to and received from a client.
+//!
+//! Captures are started with [`Client::start_capture`] and can be read back
+//! with [`read_capture`]. This module is only available with the
+//! `packet_capture` feature enabled.
+//!
+//! # Format
+//!
+//! A capture file begins with the four magic bytes `VCAP` followed by a
+//! single byte containing the format version, which is currently `1`. The
+//! rest of the file is a sequence of records with the following layout:
+//!
+//! | Field     | Type   | Description                                          |
+//! |-----------|--------|------------------------------------------------------|
+//! | Direction | u8     | `0` for serverbound packets, `1` for clientbound.    |
+//! | Time      | u64    | Microseconds since the capture started (big endian). |
+//! | Length    | VarInt | The length of the packet data.                       |
+//! | Data      | bytes  | The uncompressed packet ID and body.                 |
+//!
+//! Records appear in the order the packets were queued or received, which is
+//! not always the order they were sent in.
+//!
+//! [`Client::start_capture`]: crate::client::Client::start_capture
+
+use std::fs::File;
+use std::io::{BufWriter, Read, Write};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use anyhow::{bail, ensure};
+use tracing::warn;
+use valence_protocol::packets::S2cPlayPacket;
+use valence_protocol::{Decode, Encode, PacketDecoder, VarInt};
+
+const MAGIC: &[u8; 4] = b"VCAP";
+const VERSION: u8 = 1;
+
+/// The direction a captured packet was traveling in.
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+pub enum PacketDirection {
+    /// Sent from the client to the server.
+    Serverbound,
+    /// Sent from the server to the client.
+    Clientbound,
+}
+
+/// A packet read from a capture with [`read_capture`].
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct CapturedPacket {
+    pub direction: PacketDirection,
+    /// The time since the capture started.
+    pub elapsed: Duration,
+    /// The packet ID and body. This can be decoded with
+    /// [`S2cPlayPacket`] or
+    /// [`C2sPlayPacket`](valence_protocol::packets::C2sPlayPacket) depending on
+    /// the direction.
+    pub data: Vec<u8>,
+}
+
+/// Reads every packet from a capture in the [format](self#format) written by
+/// [`Client::start_capture`](crate::client::Client::start_capture).
+pub fn read_capture(mut r: impl Read) -> anyhow::Result<Vec<CapturedPacket>> {
+    let mut bytes = vec![];
+    r.read_to_end(&mut bytes)?;
+
+    ensure!(bytes.starts_with(MAGIC), "not a packet capture");
+
+    let mut r = &bytes[MAGIC.len()..];
+
+    let version = u8::decode(&mut r)?;
+    ensure!(
+        version == VERSION,
+        "unsupported packet capture version {version}"
+    );
+
+    let mut packets = vec![];
+
+    while !r.is_empty() {
+        let direction = match u8::decode(&mut r)? {
+            0 => PacketDirection::Serverbound,
+            1 => PacketDirection::Clientbound,
+            n => bail!("invalid packet direction {n}"),
+        };
+
+        let elapsed = Duration::from_micros(u64::decode(&mut r)?);
+        let len = VarInt::decode(&mut r)?.0;
+
+        ensure!(
+            len >= 0 && len as usize <= r.len(),
+            "invalid packet length {len}"
+        );
+
+        let (data, rest) = r.split_at(len as usize);
+        r = rest;
+
+        packets.push(CapturedPacket {
+            direction,
+            elapsed,
+            data: data.to_vec(),
+        });
+    }
+
+    Ok(packets)
+}
+
+/// A handle to a capture file shared between a client's packet sender and
+/// receiver.
+#[derive(Clone)]
+pub(crate) struct PacketCapture(Arc<Mutex<CaptureFile>>);
+
+struct CaptureFile {
+    writer: BufWriter<File>,
+    start: Instant,
+    /// Reusable buffer for encoding packets.
+    scratch: Vec<u8>,
+}
+
+impl PacketCapture {
+    /// Creates the capture file at `path`, replacing it if it exists.
+    pub fn create(path: impl AsRef<Path>) -> std::io::Result<Self> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        writer.write_all(MAGIC)?;
+        writer.write_all(&[VERSION])?;
+
+        Ok(Self(Arc::new(Mutex::new(CaptureFile {
+            writer,
+            start: Instant::now(),
+            scratch: vec![],
+        }))))
+    }
+
+    /// Records a single packet.
+    pub fn record(&self, direction: PacketDirection, pkt: &(impl Encode + ?Sized)) {
+        let mut file = self.0.lock().unwrap();
+        let file = &mut *file;
+
+        file.scratch.clear();
+        if let Err(e) = pkt.encode(&mut file.scratch) {
+            warn!("failed to encode captured packet: {e:#}");
+            return;
+        }
+
+        if let Err(e) = file.write_record(direction) {
+            warn!("failed to write packet capture: {e:#}");
+        }
+    }
+
+    /// Records the clientbound packets in `bytes`, which contains whole packet
+    /// frames that are compressed if `compression` is true.
+    pub fn record_frames(&self, bytes: &[u8], compression: bool) {
+        let mut dec = PacketDecoder::new();
+        dec.set_compression(compression);
+        dec.queue_slice(bytes);
+
+        loop {
+            match dec.try_next_packet::<S2cPlayPacket>() {
+                Ok(Some(pkt)) => self.record(PacketDirection::Clientbound, &pkt),
+                Ok(None) => break,
+                Err(e) => {
+                    warn!("failed to decode captured packet: {e:#}");
+                    break;
+                }
+            }
+        }
+    }
+
+    /// Writes any buffered records to the capture file.
+    pub fn flush(&self) -> std::io::Result<()> {
+        self.0.lock().unwrap().writer.flush()
+    }
+}
+
+impl CaptureFile {
+    /// Writes the packet in `scratch` as a record.
+    fn write_record(&mut self, direction: PacketDirection) -> anyhow::Result<()> {
+        let direction = match direction {
+            PacketDirection::Serverbound => 0_u8,
+            PacketDirection::Clientbound => 1_u8,
+        };
+        let elapsed = self.start.elapsed().as_micros() as u64;
+
+        direction.encode(&mut self.writer)?;
+        elapsed.encode(&mut self.writer)?;
+        VarInt(self.scratch.len() as i32).encode(&mut self.writer)?;
+        self.writer.write_all(&self.scratch)?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,156 @@
+//! Particles spawned with [`Client::spawn_particle`].
+//!
+//! [`Client::spawn_particle`]: crate::client::Client::spawn_particle
+
+use std::io::Write;
+
+use valence_protocol::block::BlockState;
+use valence_protocol::{Encode, ItemStack, VarInt};
+use vek::Vec3;
+
+/// A particle type along with the data it needs to be displayed.
+///
+/// The `minecraft:vibration` particle is not supported.
+#[derive(Clone, PartialEq, Debug)]
+pub enum Particle {
+    AmbientEntityEffect,
+    AngryVillager,
+    Block(BlockState),
+    BlockMarker(BlockState),
+    Bubble,
+    Cloud,
+    Crit,
+    DamageIndicator,
+    DragonBreath,
+    DrippingLava,
+    FallingLava,
+    LandingLava,
+    DrippingWater,
+    FallingWater,
+    Dust {
+        /// The red, green and blue components of the color in `0.0..=1.0`.
+        rgb: Vec3<f32>,
+        /// The size of the particle in `0.01..=4.0`.
+        scale: f32,
+    },
+    DustColorTransition {
+        /// The color the particle starts with.
+        from_rgb: Vec3<f32>,
+        scale: f32,
+        /// The color the particle fades to.
+        to_rgb: Vec3<f32>,
+    },
+    Effect,
+    ElderGuardian,
+    EnchantedHit,
+    Enchant,
+    EndRod,
+    EntityEffect,
+    ExplosionEmitter,
+    Explosion,
+    SonicBoom,
+    FallingDust(BlockState),
+    Firework,
+    Fishing,
+    Flame,
+    SculkSoul,
+    SculkCharge {
+        /// The rotation of the particle in radians.
+        roll: f32,
+    },
+    SculkChargePop,
+    SoulFireFlame,
+    Soul,
+    Flash,
+    HappyVillager,
+    Composter,
+    Heart,
+    InstantEffect,
+    Item(Option<ItemStack>),
+    ItemSlime,
+    ItemSnowball,
+    LargeSmoke,
+    Lava,
+    Mycelium,
+    Note,
+    Poof,
+    Portal,
+    Rain,
+    Smoke,
+    Sneeze,
+    Spit,
+    SquidInk,
+    SweepAttack,
+    TotemOfUndying,
+    Underwater,
+    Splash,
+    Witch,
+    BubblePop,
+    CurrentDown,
+    BubbleColumnUp,
+    Nautilus,
+    Dolphin,
+    CampfireCosySmoke,
+    CampfireSignalSmoke,
+    DrippingHoney,
+    FallingHoney,
+    LandingHoney,
+    FallingNectar,
+    FallingSporeBlossom,
+    Ash,
+    CrimsonSpore,
+    WarpedSpore,
+    SporeBlossomAir,
+    DrippingObsidianTear,
+    FallingObsidianTear,
+    LandingObsidianTear,
+    ReversePortal,
+    WhiteAsh,
+    SmallFlame,
+    Snowflake,
+    DrippingDripstoneLava,
+    FallingDripstoneLava,
+    DrippingDripstoneWater,
+    FallingDripstoneWater,
+    GlowSquidInk,
+    Glow,
+    WaxOn,
+    WaxOff,
+    ElectricSpark,
+    Scrape,
+    Shriek {
+        /// The number of ticks before the particle appears.
+        delay: i32,
+    },
+}
+
+include!(concat!(env!("OUT_DIR"), "/particle.rs"));
+
+impl Particle {
+    /// Writes the data which follows the particle ID in the particle packet.
+    /// Nothing is written for particles without data.
+    pub(crate) fn encode_data(&self, mut w: impl Write) -> anyhow::Result<()> {
+        match self {
+            Particle::Block(block_state) => block_state.encode(w),
+            Particle::BlockMarker(block_state) => block_state.encode(w),
+            Particle::Dust { rgb, scale } => {
+                rgb.into_array().encode(&mut w)?;
+                scale.encode(w)
+            }
+            Particle::DustColorTransition {
+                from_rgb,
+                scale,
+                to_rgb,
+            } => {
+                from_rgb.into_array().encode(&mut w)?;
+                scale.encode(&mut w)?;
+                to_rgb.into_array().encode(w)
+            }
+            Particle::FallingDust(block_state) => block_state.encode(w),
+            Particle::SculkCharge { roll } => roll.encode(w),
+            Particle::Item(stack) => stack.encode(w),
+            Particle::Shriek { delay } => VarInt(*delay).encode(w),
+            _ => Ok(()),
+        }
+    }
+}
@@ -8,7 +8,7 @@ use bitfield_struct::bitfield;
 use uuid::Uuid;
 use valence_protocol::packets::s2c::play::{PlayerInfo, SetTabListHeaderAndFooter};
 use valence_protocol::types::{GameMode, PlayerInfoAddPlayer, SignedProperty};
-use valence_protocol::Text;
+use valence_protocol::{Text, VarInt};
 
 use crate::config::Config;
 use crate::packet::{PacketWriter, WritePacket};
@@ -18,6 +18,7 @@ use crate::slab_rc::{Key, RcSlab};
 /// A container for all [`PlayerList`]s on a server.
 pub struct PlayerLists<C: Config> {
     slab: RcSlab<PlayerList<C>>,
+    compression_threshold: Option<u32>,
 }
 
 /// An identifier for a [`PlayerList`] on the server.
@@ -32,9 +33,10 @@ pub struct PlayerLists<C: Config> {
 pub struct PlayerListId(Key);
 
 impl<C: Config> PlayerLists<C> {
-    pub(crate) fn new() -> Self {
+    pub(crate) fn new(compression_threshold: Option<u32>) -> Self {
         Self {
             slab: RcSlab::new(),
+            compression_threshold,
         }
     }
 
@@ -47,6 +49,7 @@ impl<C: Config> PlayerLists<C> {
         let (key, pl) = self.slab.insert(PlayerList {
             state,
             cached_update_packets: vec![],
+            compression_threshold: self.compression_threshold,
             entries: HashMap::new(),
             removed: HashSet::new(),
             header: Text::default(),
@@ -73,34 +76,56 @@ impl<C: Config> PlayerLists<C> {
         self.slab.get_mut(&id.0)
     }
 
-    pub(crate) fn update_caches(&mut self, compression_threshold: Option<u32>) {
+    pub(crate) fn update_caches(&mut self) {
         let mut scratch = vec![];
 
-        // Cache the update packets.
+        // Cache the update packets. Packets written by `PlayerList::send_ping`
+        // earlier in the tick are kept.
         for pl in self.slab.iter_mut() {
-            pl.cached_update_packets.clear();
-
             let mut writer = PacketWriter::new(
                 &mut pl.cached_update_packets,
-                compression_threshold,
+                pl.compression_threshold,
                 &mut scratch,
             );
 
-            if !pl.removed.is_empty() {
-                writer
-                    .write_packet(&PlayerInfo::RemovePlayer(
-                        pl.removed.iter().cloned().collect(),
-                    ))
-                    .unwrap();
-            }
-
-            let mut add_player = vec![];
+            let mut remove_player: Vec<_> = pl.removed.iter().cloned().collect();
+            let mut added = vec![];
             let mut game_mode = vec![];
             let mut ping = vec![];
             let mut display_name = vec![];
 
-            for (&uuid, entry) in pl.entries.iter_mut() {
-                if entry.bits.created_this_tick() {
+            for (&uuid, entry) in &pl.entries {
+                // Entries with a new sort key are removed and added again so
+                // that clients receive them in the new order.
+                let readd = entry.bits.modified_display_order() && !entry.bits.created_this_tick();
+
+                if readd {
+                    remove_player.push(uuid);
+                }
+
+                if entry.bits.created_this_tick() || readd {
+                    added.push((uuid, entry));
+                } else {
+                    if entry.bits.modified_game_mode() {
+                        game_mode.push((uuid, entry.game_mode));
+                    }
+
+                    if entry.bits.modified_ping() {
+                        ping.push((uuid, entry.ping.into()));
+                    }
+
+                    if entry.bits.modified_display_name() {
+                        display_name.push((uuid, entry.display_name.clone()));
+                    }
+                }
+            }
+
+            // Only the entries (re)added this tick need to be put in order.
+            added.sort_unstable_by(|(_, a), (_, b)| a.sort_key().cmp(&b.sort_key()));
+
+            let add_player: Vec<_> = added
+                .into_iter()
+                .map(|(uuid, entry)| {
                     let mut properties = vec![];
                     if let Some(textures) = &entry.textures {
                         properties.push(SignedProperty {
@@ -110,7 +135,7 @@ impl<C: Config> PlayerLists<C> {
                         });
                     }
 
-                    add_player.push(PlayerInfoAddPlayer {
+                    PlayerInfoAddPlayer {
                         uuid,
                         username: &entry.username,
                         properties,
@@ -118,22 +143,14 @@ impl<C: Config> PlayerLists<C> {
                         ping: entry.ping.into(),
                         display_name: entry.display_name.clone(),
                         sig_data: None,
-                    });
-                } else {
-                    if entry.bits.modified_game_mode() {
-                        game_mode.push((uuid, entry.game_mode));
-                    }
-
-                    if entry.bits.modified_ping() {
-                        ping.push((uuid, entry.ping.into()));
                     }
+                })
+                .collect();
 
-                    if entry.bits.modified_display_name() {
-                        display_name.push((uuid, entry.display_name.clone()));
-                    }
-                }
-
-                entry.bits = EntryBits::new();
+            if !remove_player.is_empty() {
+                writer
+                    .write_packet(&PlayerInfo::RemovePlayer(remove_player))
+                    .unwrap();
             }
 
             if !add_player.is_empty() {
@@ -170,11 +187,16 @@ impl<C: Config> PlayerLists<C> {
                     })
                     .unwrap();
             }
+
+            for entry in pl.entries.values_mut() {
+                entry.bits = EntryBits::new();
+            }
         }
     }
 
-    pub(crate) fn clear_removed(&mut self) {
+    pub(crate) fn update(&mut self) {
         for pl in self.slab.iter_mut() {
+            pl.cached_update_packets.clear();
             pl.removed.clear();
         }
     }
@@ -205,6 +227,7 @@ pub struct PlayerList<C: Config> {
     /// Custom state
     pub state: C::PlayerListState,
     cached_update_packets: Vec<u8>,
+    compression_threshold: Option<u32>,
     entries: HashMap<Uuid, PlayerListEntry>,
     removed: HashSet<Uuid>,
     header: Text,
@@ -230,7 +253,9 @@ impl<C: Config> PlayerList<C> {
     /// Inserts a player into the player list.
     ///
     /// If the given UUID conflicts with an existing entry, the entry is
-    /// overwritten and `false` is returned. Otherwise, `true` is returned.
+    /// overwritten and `false` is returned. Otherwise, `true` is returned. The
+    /// [display order](PlayerListEntry::set_display_order) of an overwritten
+    /// entry is kept.
     pub fn insert(
         &mut self,
         uuid: Uuid,
@@ -250,12 +275,15 @@ impl<C: Config> PlayerList<C> {
                         self.removed.insert(*oe.key());
                     }
 
+                    let display_order = e.display_order.take();
+
                     oe.insert(PlayerListEntry {
                         username,
                         textures,
                         game_mode,
                         ping,
                         display_name,
+                        display_order,
                         bits: EntryBits::new().with_created_this_tick(true),
                     });
                 } else {
@@ -272,6 +300,7 @@ impl<C: Config> PlayerList<C> {
                     game_mode,
                     ping,
                     display_name,
+                    display_order: None,
                     bits: EntryBits::new().with_created_this_tick(true),
                 });
                 true
@@ -309,6 +338,41 @@ impl<C: Config> PlayerList<C> {
         self.removed.extend(self.entries.drain().map(|p| p.0))
     }
 
+    /// Sets the ping (latency) of the entry with the given UUID, measured in
+    /// milliseconds, and immediately writes the packet that updates it for
+    /// the clients viewing this player list. Returns whether the entry was
+    /// present in the list.
+    ///
+    /// Unlike [`PlayerListEntry::set_ping`], the change is not collected with
+    /// the other changes to the list, so frequent ping updates for a few
+    /// entries do not wait for the update packets of the whole list to be
+    /// rebuilt. Clients receive the packet with the rest of this tick's
+    /// updates.
+    pub fn send_ping(&mut self, uuid: Uuid, ping: i32) -> bool {
+        let Some(entry) = self.entries.get_mut(&uuid) else {
+            return false;
+        };
+
+        entry.ping = ping;
+        entry.bits.set_modified_ping(false);
+
+        // Entries added this tick are sent with their latest ping anyway.
+        if !entry.bits.created_this_tick() {
+            let mut scratch = vec![];
+            let mut writer = PacketWriter::new(
+                &mut self.cached_update_packets,
+                self.compression_threshold,
+                &mut scratch,
+            );
+
+            writer
+                .write_packet(&PlayerInfo::UpdateLatency(vec![(uuid, VarInt(ping))]))
+                .unwrap();
+        }
+
+        true
+    }
+
     /// Gets the header part of the player list.
     pub fn header(&self) -> &Text {
         &self.header
@@ -363,9 +427,11 @@ impl<C: Config> PlayerList<C> {
 
     /// Writes the packets needed to completely initialize this player list.
     pub(crate) fn write_init_packets(&self, mut writer: impl WritePacket) -> anyhow::Result<()> {
-        let add_player: Vec<_> = self
-            .entries
-            .iter()
+        let mut entries: Vec<_> = self.entries.iter().collect();
+        entries.sort_unstable_by(|(_, a), (_, b)| a.sort_key().cmp(&b.sort_key()));
+
+        let add_player: Vec<_> = entries
+            .into_iter()
             .map(|(&uuid, entry)| PlayerInfoAddPlayer {
                 uuid,
                 username: &entry.username,
@@ -393,7 +459,7 @@ impl<C: Config> PlayerList<C> {
                 .unwrap();
         }
 
-        if self.header.is_empty() || self.footer.is_empty() {
+        if !self.header.is_empty() || !self.footer.is_empty() {
             writer
                 .write_packet(&SetTabListHeaderAndFooter {
                     header: self.header.clone(),
@@ -431,6 +497,7 @@ pub struct PlayerListEntry {
     game_mode: GameMode,
     ping: i32,
     display_name: Option<Text>,
+    display_order: Option<String>,
     bits: EntryBits,
 }
 
@@ -440,7 +507,8 @@ struct EntryBits {
     modified_game_mode: bool,
     modified_ping: bool,
     modified_display_name: bool,
-    #[bits(4)]
+    modified_display_order: bool,
+    #[bits(3)]
     _pad: u8,
 }
 
@@ -494,4 +562,181 @@ impl PlayerListEntry {
             self.bits.set_modified_display_name(true);
         }
     }
+
+    /// Gets the sort key of this entry.
+    pub fn display_order(&self) -> Option<&str> {
+        self.display_order.as_deref()
+    }
+
+    /// Sets the sort key of this entry.
+    ///
+    /// Entries are sent to clients in ascending order of their sort key and
+    /// then their username. Entries without a sort key come first. Changing
+    /// the sort key removes the entry from the list and adds it again so that
+    /// clients receive it in the new order.
+    pub fn set_display_order(&mut self, display_order: impl Into<Option<String>>) {
+        let display_order = display_order.into();
+        if self.display_order != display_order {
+            self.display_order = display_order;
+            self.bits.set_modified_display_order(true);
+        }
+    }
+
+    /// The key entries are sorted by when they are sent to clients.
+    fn sort_key(&self) -> (Option<&str>, &str) {
+        (self.display_order.as_deref(), &self.username)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use valence_protocol::packets::S2cPlayPacket;
+
+    use super::*;
+    use crate::packet::decode_packets;
+
+    type MockConfig = crate::config::MockConfig;
+
+    /// Returns the bytes of the packets needed to initialize `list`.
+    fn init_packets(list: &PlayerList<MockConfig>) -> Vec<u8> {
+        let mut bytes = vec![];
+        let mut compression_scratch = vec![];
+        let writer = PacketWriter::new(&mut bytes, None, &mut compression_scratch);
+        list.write_init_packets(writer).unwrap();
+        bytes
+    }
+
+    /// Returns the header and footer sent in the init packets of `list`, if
+    /// any.
+    fn init_header_and_footer(list: &PlayerList<MockConfig>) -> Option<(Text, Text)> {
+        let mut header_and_footer = None;
+
+        for pkt in decode_packets(&init_packets(list)) {
+            if let S2cPlayPacket::SetTabListHeaderAndFooter(p) = pkt {
+                assert!(header_and_footer.is_none());
+                header_and_footer = Some((p.header, p.footer));
+            }
+        }
+
+        header_and_footer
+    }
+
+    /// Returns the UUIDs removed and the usernames added by the player info
+    /// packets in `bytes`, in the order they were written.
+    fn player_info_changes(bytes: &[u8]) -> (Vec<Uuid>, Vec<String>) {
+        let mut removed = vec![];
+        let mut added = vec![];
+
+        for pkt in decode_packets(bytes) {
+            match pkt {
+                S2cPlayPacket::PlayerInfo(PlayerInfo::RemovePlayer(uuids)) => removed.extend(uuids),
+                S2cPlayPacket::PlayerInfo(PlayerInfo::AddPlayer(players)) => {
+                    added.extend(players.iter().map(|p| p.username.to_owned()))
+                }
+                _ => {}
+            }
+        }
+
+        (removed, added)
+    }
+
+    #[test]
+    fn entries_are_sent_in_display_order() {
+        let mut lists = PlayerLists::<MockConfig>::new(None);
+        let (id, list) = lists.insert(());
+
+        let alice = Uuid::from_u128(1);
+        let bob = Uuid::from_u128(2);
+        let carol = Uuid::from_u128(3);
+
+        for (uuid, name) in [(alice, "alice"), (bob, "bob"), (carol, "carol")] {
+            list.insert(uuid, name, None, GameMode::Survival, 0, None);
+        }
+
+        list.entry_mut(alice)
+            .unwrap()
+            .set_display_order("b".to_owned());
+        list.entry_mut(bob)
+            .unwrap()
+            .set_display_order("a".to_owned());
+
+        lists.update_caches();
+
+        let expected = ["carol", "bob", "alice"];
+        let list = lists.get(&id);
+        assert_eq!(
+            player_info_changes(&list.cached_update_packets),
+            (vec![], expected.map(String::from).to_vec())
+        );
+
+        assert_eq!(player_info_changes(&init_packets(list)).1, expected);
+
+        // Changing the sort key adds the entry again.
+        lists.update();
+        let list = lists.get_mut(&id);
+        list.entry_mut(alice)
+            .unwrap()
+            .set_display_order("0".to_owned());
+
+        // Overwriting an entry keeps its sort key.
+        list.insert(bob, "robert", None, GameMode::Survival, 0, None);
+        assert_eq!(list.entry(bob).unwrap().display_order(), Some("a"));
+
+        lists.update_caches();
+
+        assert_eq!(
+            player_info_changes(&lists.get(&id).cached_update_packets),
+            (
+                vec![bob, alice],
+                vec!["alice".to_owned(), "robert".to_owned()]
+            )
+        );
+
+        assert_eq!(
+            player_info_changes(&init_packets(lists.get(&id))).1,
+            ["carol", "alice", "robert"]
+        );
+    }
+
+    #[test]
+    fn send_ping_writes_single_latency_update() {
+        let mut lists = PlayerLists::<MockConfig>::new(None);
+        let (id, list) = lists.insert(());
+
+        let alice = Uuid::from_u128(1);
+        let bob = Uuid::from_u128(2);
+        list.insert(alice, "alice", None, GameMode::Survival, 0, None);
+        list.insert(bob, "bob", None, GameMode::Survival, 0, None);
+
+        lists.update_caches();
+        lists.update();
+
+        let list = lists.get_mut(&id);
+        assert!(list.send_ping(alice, 42));
+        assert!(!list.send_ping(Uuid::from_u128(3), 42));
+        assert_eq!(list.entry(alice).unwrap().ping(), 42);
+
+        lists.update_caches();
+
+        let packets = decode_packets(&lists.get(&id).cached_update_packets);
+        assert!(matches!(
+            packets.as_slice(),
+            [S2cPlayPacket::PlayerInfo(PlayerInfo::UpdateLatency(entries))]
+                if entries == &[(alice, VarInt(42))]
+        ));
+    }
+
+    #[test]
+    fn init_packets_include_header_without_footer() {
+        let mut lists = PlayerLists::<MockConfig>::new(None);
+        let (_, list) = lists.insert(());
+
+        assert_eq!(init_header_and_footer(list), None);
+
+        list.set_header("Welcome");
+        assert_eq!(
+            init_header_and_footer(list),
+            Some(("Welcome".into(), Text::default()))
+        );
+    }
 }
@@ -1,15 +1,19 @@
 //! The heart of the server.
 
+use std::collections::VecDeque;
 use std::error::Error;
 use std::iter::FusedIterator;
 use std::net::{IpAddr, SocketAddr};
 use std::ops::{Deref, DerefMut};
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::{Arc, Mutex};
 use std::time::{Duration, Instant};
 use std::{io, thread};
 
 use anyhow::{ensure, Context};
 use flume::{Receiver, Sender};
+#[cfg(test)]
+pub(crate) use packet_manager::MockConnection;
 pub(crate) use packet_manager::{PlayPacketReceiver, PlayPacketSender};
 use rand::rngs::OsRng;
 use rayon::iter::ParallelIterator;
@@ -30,13 +34,14 @@ use valence_protocol::packets::s2c::login::{DisconnectLogin, LoginSuccess, SetCo
 use valence_protocol::packets::s2c::status::{PingResponse, StatusResponse};
 use valence_protocol::types::HandshakeNextState;
 use valence_protocol::{
-    ident, PacketDecoder, PacketEncoder, Username, VarInt, MINECRAFT_VERSION, PROTOCOL_VERSION,
+    ident, Ident, PacketDecoder, PacketEncoder, Username, VarInt, MINECRAFT_VERSION,
+    PROTOCOL_VERSION,
 };
 
 use crate::biome::{validate_biomes, Biome, BiomeId};
-use crate::chunk::entity_partition::update_entity_partition;
-use crate::client::{Client, Clients};
-use crate::config::{Config, ConnectionMode, ServerListPing};
+use crate::chunk::entity_partition::{cache_entity_init_packets, update_entity_partition};
+use crate::client::{Client, ClientId, Clients};
+use crate::config::{Config, ConnectionMode, EntityUpdateTier, ServerListPing};
 use crate::dimension::{validate_dimensions, Dimension, DimensionId};
 use crate::entity::Entities;
 use crate::inventory::Inventories;
@@ -133,6 +138,8 @@ struct SharedServerInner<C: Config> {
     _tokio_runtime: Option<Runtime>,
     dimensions: Vec<Dimension>,
     biomes: Vec<Biome>,
+    /// Sorted by ascending `min_distance`.
+    entity_update_tiers: Vec<EntityUpdateTier>,
     /// Contains info about dimensions, biomes, and chats.
     /// Sent to all clients when joining.
     registry_codec: Compound,
@@ -153,6 +160,21 @@ struct SharedServerInner<C: Config> {
     public_key_der: Box<[u8]>,
     /// For session server requests.
     http_client: ReqwestClient,
+    /// The number of clients, entities and worlds at the end of the last tick.
+    client_count: AtomicUsize,
+    entity_count: AtomicUsize,
+    world_count: AtomicUsize,
+    /// The number of loaded chunks in all worlds. Updated by the worlds'
+    /// [`Chunks`](crate::chunk::Chunks) as chunks are created and removed.
+    loaded_chunk_count: Arc<AtomicUsize>,
+    /// The number of ticks [`SharedServer::tps`] is measured over.
+    tps_window: usize,
+    /// The start times of the last `tps_window + 1` ticks.
+    tick_starts: Mutex<VecDeque<Instant>>,
+    keepalive_interval: Ticks,
+    max_missed_keepalives: u32,
+    chunk_load_assertions: bool,
+    auto_manage_player_list: bool,
 }
 
 /// Contains information about a new client joining the server.
@@ -195,6 +217,29 @@ impl<C: Config> SharedServer<C> {
         self.0.tick_rate
     }
 
+    /// Gets the configured number of ticks between keepalives.
+    pub fn keepalive_interval(&self) -> Ticks {
+        self.0.keepalive_interval
+    }
+
+    /// Gets the configured number of keepalives a client may miss in a row
+    /// before it is disconnected.
+    pub fn max_missed_keepalives(&self) -> u32 {
+        self.0.max_missed_keepalives
+    }
+
+    /// Gets whether clients check for chunks being loaded or unloaded twice in
+    /// debug builds. See [`Config::chunk_load_assertions`].
+    pub fn chunk_load_assertions(&self) -> bool {
+        self.0.chunk_load_assertions
+    }
+
+    /// Gets whether clients are added to and removed from player lists
+    /// automatically. See [`Config::auto_manage_player_list`].
+    pub fn auto_manage_player_list(&self) -> bool {
+        self.0.auto_manage_player_list
+    }
+
     /// Gets the connection mode of the server.
     pub fn connection_mode(&self) -> &ConnectionMode {
         &self.0.connection_mode
@@ -240,6 +285,9 @@ impl<C: Config> SharedServer<C> {
 
     /// Returns an iterator over all added dimensions and their associated
     /// [`DimensionId`].
+    ///
+    /// The dimensions are always in the same order as the `Vec` returned by
+    /// [`Config::dimensions`], so the `n`th dimension has the `n`th ID.
     pub fn dimensions(&self) -> impl FusedIterator<Item = (DimensionId, &Dimension)> + Clone {
         self.0
             .dimensions
@@ -248,6 +296,23 @@ impl<C: Config> SharedServer<C> {
             .map(|(i, d)| (DimensionId(i as u16), d))
     }
 
+    /// Obtains a [`Dimension`] and its [`DimensionId`] by using the
+    /// dimension's name as returned by [`DimensionId::dimension_name`].
+    ///
+    /// If there is no dimension with the name, `None` is returned.
+    pub fn dimension_by_name(&self, name: Ident<&str>) -> Option<(DimensionId, &Dimension)> {
+        self.dimensions()
+            .find(|(id, _)| id.dimension_name() == name)
+    }
+
+    /// Obtains the [`DimensionId`] of the dimension with the given name as
+    /// returned by [`DimensionId::dimension_name`].
+    ///
+    /// If there is no dimension with the name, `None` is returned.
+    pub fn dimension_id_of(&self, name: Ident<&str>) -> Option<DimensionId> {
+        self.dimension_by_name(name).map(|(id, _)| id)
+    }
+
     /// Obtains a [`Biome`] by using its corresponding [`BiomeId`].
     ///
     /// It is safe but unspecified behavior to call this function using a
@@ -270,6 +335,18 @@ impl<C: Config> SharedServer<C> {
             .map(|(i, b)| (BiomeId(i as u16), b))
     }
 
+    /// Gets the number of ticks between movement updates for entities the
+    /// given number of chunks away from a client. See
+    /// [`Config::entity_update_tiers`].
+    pub fn entity_update_interval(&self, distance: u8) -> Ticks {
+        self.0
+            .entity_update_tiers
+            .iter()
+            .rev()
+            .find(|tier| distance >= tier.min_distance)
+            .map_or(1, |tier| tier.interval)
+    }
+
     pub(crate) fn registry_codec(&self) -> &Compound {
         &self.0.registry_codec
     }
@@ -279,6 +356,71 @@ impl<C: Config> SharedServer<C> {
         self.0.start_instant
     }
 
+    /// Returns the number of clients on the server as of the end of the last
+    /// tick. This includes clients which have disconnected but have not been
+    /// removed.
+    pub fn client_count(&self) -> usize {
+        self.0.client_count.load(Ordering::Relaxed)
+    }
+
+    /// Returns the number of entities on the server as of the end of the last
+    /// tick.
+    pub fn entity_count(&self) -> usize {
+        self.0.entity_count.load(Ordering::Relaxed)
+    }
+
+    /// Returns the number of worlds on the server as of the end of the last
+    /// tick.
+    pub fn world_count(&self) -> usize {
+        self.0.world_count.load(Ordering::Relaxed)
+    }
+
+    /// Returns the number of loaded chunks in all worlds on the server.
+    ///
+    /// Unlike the other counts, this is updated as soon as chunks are created
+    /// or removed. Chunks marked as deleted are counted until the end of the
+    /// tick.
+    pub fn loaded_chunk_count(&self) -> usize {
+        self.0.loaded_chunk_count.load(Ordering::Relaxed)
+    }
+
+    pub(crate) fn loaded_chunk_counter(&self) -> Arc<AtomicUsize> {
+        self.0.loaded_chunk_count.clone()
+    }
+
+    /// Returns the number of ticks per second the server has measurably been
+    /// running at over the last [`Config::tps_window`] ticks.
+    ///
+    /// A server which is keeping up reports close to its
+    /// [tick rate](Self::tick_rate). The tick rate is returned until at least
+    /// two ticks have started.
+    pub fn tps(&self) -> f64 {
+        let tick_starts = self.0.tick_starts.lock().unwrap();
+
+        match (tick_starts.front(), tick_starts.back()) {
+            (Some(&first), Some(&last)) if tick_starts.len() > 1 => {
+                let elapsed = (last - first).as_secs_f64();
+                if elapsed > 0.0 {
+                    (tick_starts.len() - 1) as f64 / elapsed
+                } else {
+                    self.0.tick_rate as f64
+                }
+            }
+            _ => self.0.tick_rate as f64,
+        }
+    }
+
+    /// Records the start of a tick for [`Self::tps`].
+    fn record_tick_start(&self, now: Instant) {
+        let mut tick_starts = self.0.tick_starts.lock().unwrap();
+
+        if tick_starts.len() > self.0.tps_window {
+            tick_starts.pop_front();
+        }
+
+        tick_starts.push_back(now);
+    }
+
     /// Immediately stops new connections to the server and initiates server
     /// shutdown. The given result is returned through [`start_server`].
     ///
@@ -310,7 +452,7 @@ pub fn start_server<C: Config>(config: C, data: C::ServerState) -> ShutdownResul
         clients: Clients::new(),
         entities: Entities::new(),
         worlds: Worlds::new(shared.clone()),
-        player_lists: PlayerLists::new(),
+        player_lists: PlayerLists::new(shared.compression_threshold()),
         inventories: Inventories::new(),
         current_tick: 0,
         last_tick_duration: Default::default(),
@@ -324,13 +466,30 @@ pub fn start_server<C: Config>(config: C, data: C::ServerState) -> ShutdownResul
 }
 
 #[instrument(skip_all)]
-fn setup_server<C: Config>(cfg: C) -> anyhow::Result<SharedServer<C>> {
+pub(crate) fn setup_server<C: Config>(cfg: C) -> anyhow::Result<SharedServer<C>> {
     let max_connections = cfg.max_connections();
     let address = cfg.address();
     let tick_rate = cfg.tick_rate();
 
     ensure!(tick_rate > 0, "tick rate must be greater than zero");
 
+    let tps_window = cfg.tps_window();
+
+    ensure!(tps_window > 0, "TPS window must be greater than zero");
+
+    let keepalive_interval = cfg.keepalive_interval();
+
+    ensure!(
+        keepalive_interval > 0,
+        "keepalive interval must be greater than zero"
+    );
+
+    let max_missed_keepalives = cfg.max_missed_keepalives();
+
+    let chunk_load_assertions = cfg.chunk_load_assertions();
+
+    let auto_manage_player_list = cfg.auto_manage_player_list();
+
     let connection_mode = cfg.connection_mode();
 
     let incoming_packet_capacity = cfg.incoming_capacity();
@@ -357,6 +516,13 @@ fn setup_server<C: Config>(cfg: C) -> anyhow::Result<SharedServer<C>> {
     let biomes = cfg.biomes();
     validate_biomes(&biomes)?;
 
+    let mut entity_update_tiers = cfg.entity_update_tiers();
+    ensure!(
+        entity_update_tiers.iter().all(|tier| tier.interval > 0),
+        "entity update intervals must be greater than zero"
+    );
+    entity_update_tiers.sort_by_key(|tier| tier.min_distance);
+
     let rsa_key = RsaPrivateKey::new(&mut OsRng, 1024)?;
 
     let public_key_der =
@@ -391,6 +557,7 @@ fn setup_server<C: Config>(cfg: C) -> anyhow::Result<SharedServer<C>> {
         _tokio_runtime: runtime,
         dimensions,
         biomes,
+        entity_update_tiers,
         registry_codec,
         start_instant: Instant::now(),
         new_clients_send,
@@ -400,6 +567,16 @@ fn setup_server<C: Config>(cfg: C) -> anyhow::Result<SharedServer<C>> {
         rsa_key,
         public_key_der,
         http_client: ReqwestClient::new(),
+        client_count: AtomicUsize::new(0),
+        entity_count: AtomicUsize::new(0),
+        world_count: AtomicUsize::new(0),
+        loaded_chunk_count: Arc::new(AtomicUsize::new(0)),
+        tps_window,
+        tick_starts: Mutex::new(VecDeque::with_capacity(tps_window + 1)),
+        keepalive_interval,
+        max_missed_keepalives,
+        chunk_load_assertions,
+        auto_manage_player_list,
     };
 
     Ok(SharedServer(Arc::new(server)))
@@ -437,49 +614,69 @@ fn do_update_loop(server: &mut Server<impl Config>) -> ShutdownResult {
     let shared = server.shared.clone();
 
     let threshold = shared.0.compression_threshold;
+    // Throttled movement is only sent when some tier holds movement back.
+    let throttled_updates = shared
+        .0
+        .entity_update_tiers
+        .iter()
+        .any(|tier| tier.interval > 1);
 
     loop {
         let _span = info_span!("update_loop", tick = server.current_tick).entered();
 
+        shared.record_tick_start(tick_start);
+
         if let Some(res) = shared.0.shutdown_result.lock().unwrap().take() {
             return res;
         }
 
-        for _ in 0..shared.0.new_clients_recv.len() {
-            let Ok(msg) = shared.0.new_clients_recv.try_recv() else {
-                break
-            };
-
-            info!(
-                username = %msg.ncd.username,
-                uuid = %msg.ncd.uuid,
-                ip = %msg.ncd.ip,
-                "inserting client"
-            );
-
-            server.clients.insert(Client::new(
-                msg.send,
-                msg.recv,
-                msg.permit,
-                msg.ncd,
-                Default::default(),
-            ));
-        }
+        let joined = insert_new_clients(server);
 
         // Get serverbound packets first so they are not dealt with a tick late.
         for (_, client) in server.clients.iter_mut() {
             client.prepare_c2s_packets();
         }
 
+        notify_joins_and_leaves(server, &joined);
+
         info_span!("configured_update").in_scope(|| shared.config().update(server));
 
-        update_entity_partition(&mut server.entities, &mut server.worlds, threshold);
+        if shared.auto_manage_player_list() {
+            server
+                .clients
+                .update_player_list_entries(&mut server.player_lists);
+        }
+
+        for (_, client) in server.clients.iter_mut() {
+            client.update_held_item_equipment(&mut server.entities);
+            client.update_broadcast_player_data(&mut server.entities);
+        }
+
+        server.entities.update_passengers();
+
+        update_entity_partition(
+            &mut server.entities,
+            &mut server.worlds,
+            threshold,
+            throttled_updates,
+            server.current_tick,
+        );
 
         for (_, world) in server.worlds.iter_mut() {
+            world.apply_scheduled_block_updates();
             world.chunks.update_caches();
         }
 
-        server.player_lists.update_caches(threshold);
+        server.player_lists.update_caches();
+
+        // Cache the init packets of the cells clients are about to load so that
+        // clients loading the same cells share them.
+        server
+            .clients
+            .par_iter()
+            .for_each(|(_, client)| client.request_entity_init_packets(&server.worlds));
+
+        cache_entity_init_packets(&server.entities, &mut server.worlds, threshold);
 
         server.clients.par_iter_mut().for_each(|(_, client)| {
             client.update(
@@ -496,10 +693,12 @@ fn do_update_loop(server: &mut Server<impl Config>) -> ShutdownResult {
 
         server.worlds.update();
 
-        server.player_lists.clear_removed();
+        server.player_lists.update();
 
         server.inventories.update();
 
+        update_counts(server);
+
         // Sleep for the remainder of the tick.
         let tick_duration = Duration::from_secs_f64((shared.0.tick_rate as f64).recip());
         server.last_tick_duration = tick_start.elapsed();
@@ -510,6 +709,81 @@ fn do_update_loop(server: &mut Server<impl Config>) -> ShutdownResult {
     }
 }
 
+/// Stores the number of clients, entities and worlds in the [`SharedServer`]
+/// so they can be read from other threads.
+fn update_counts<C: Config>(server: &mut Server<C>) {
+    let shared = &server.shared.0;
+
+    shared
+        .client_count
+        .store(server.clients.len(), Ordering::Relaxed);
+    shared
+        .entity_count
+        .store(server.entities.len(), Ordering::Relaxed);
+    shared
+        .world_count
+        .store(server.worlds.len(), Ordering::Relaxed);
+}
+
+/// Inserts the clients that finished logging in since the last call and
+/// returns their IDs.
+fn insert_new_clients<C: Config>(server: &mut Server<C>) -> Vec<ClientId> {
+    let mut joined = vec![];
+
+    for _ in 0..server.shared.0.new_clients_recv.len() {
+        let Ok(msg) = server.shared.0.new_clients_recv.try_recv() else {
+            break
+        };
+
+        info!(
+            username = %msg.ncd.username,
+            uuid = %msg.ncd.uuid,
+            ip = %msg.ncd.ip,
+            "inserting client"
+        );
+
+        let registry_codec = server.shared.config().registry_codec_for(&msg.ncd);
+
+        let (id, _) = server.clients.insert(Client::new(
+            msg.send,
+            msg.recv,
+            msg.permit,
+            msg.ncd,
+            registry_codec,
+            server.current_tick,
+            Default::default(),
+        ));
+
+        joined.push(id);
+    }
+
+    joined
+}
+
+/// Calls [`Config::on_client_join`] for the clients in `joined` and
+/// [`Config::on_client_leave`] for every client that was removed or
+/// disconnected since the last call.
+fn notify_joins_and_leaves<C: Config>(server: &mut Server<C>, joined: &[ClientId]) {
+    let shared = server.shared.clone();
+
+    for &id in joined {
+        shared.config().on_client_join(server, id);
+    }
+
+    let mut left = server.clients.take_removed_unnotified();
+
+    left.extend(
+        server
+            .clients
+            .iter_mut()
+            .filter_map(|(id, client)| client.take_leave_notification().then_some(id)),
+    );
+
+    for id in left {
+        shared.config().on_client_leave(server, id);
+    }
+}
+
 #[instrument(skip_all)]
 async fn do_accept_loop(server: SharedServer<impl Config>) {
     let listener = match TcpListener::bind(server.0.address).await {
@@ -730,3 +1004,447 @@ async fn handle_login(
 
     Ok(Some(ncd))
 }
+
+#[cfg(test)]
+mod tests {
+
+    use valence_nbt::Value;
+    use valence_protocol::packets::S2cPlayPacket;
+    use valence_protocol::types::GameMode;
+
+    use super::*;
+    use crate::chunk::UnloadedChunk;
+    use crate::entity::EntityKind;
+
+    type MockConfig = crate::config::MockConfig;
+
+    struct MultiDimensionConfig;
+
+    impl Config for MultiDimensionConfig {
+        type ServerState = ();
+        type ClientState = ();
+        type EntityState = ();
+        type WorldState = ();
+        type ChunkState = ();
+        type PlayerListState = ();
+        type InventoryState = ();
+
+        fn dimensions(&self) -> Vec<Dimension> {
+            vec![Dimension::default(); 3]
+        }
+    }
+
+    #[derive(Default)]
+    struct JoinLeaveConfig {
+        joins: AtomicUsize,
+        leaves: AtomicUsize,
+    }
+
+    impl Config for JoinLeaveConfig {
+        type ServerState = ();
+        type ClientState = ();
+        type EntityState = ();
+        type WorldState = ();
+        type ChunkState = ();
+        type PlayerListState = ();
+        type InventoryState = ();
+
+        fn on_client_join(&self, server: &mut Server<Self>, client: ClientId) {
+            assert!(server.clients.get(client).is_some());
+            self.joins.fetch_add(1, Ordering::SeqCst);
+        }
+
+        fn on_client_leave(&self, server: &mut Server<Self>, client: ClientId) {
+            // Removed clients are no longer present.
+            if let Some(client) = server.clients.get(client) {
+                assert!(client.is_disconnected());
+            }
+            self.leaves.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+
+    struct AutoPlayerListConfig;
+
+    impl Config for AutoPlayerListConfig {
+        type ServerState = ();
+        type ClientState = ();
+        type EntityState = ();
+        type WorldState = ();
+        type ChunkState = ();
+        type PlayerListState = ();
+        type InventoryState = ();
+
+        fn auto_manage_player_list(&self) -> bool {
+            true
+        }
+    }
+
+    struct CustomCodecConfig;
+
+    impl Config for CustomCodecConfig {
+        type ServerState = ();
+        type ClientState = ();
+        type EntityState = ();
+        type WorldState = ();
+        type ChunkState = ();
+        type PlayerListState = ();
+        type InventoryState = ();
+
+        fn registry_codec_for(&self, ncd: &NewClientData) -> Option<Compound> {
+            let mut codec = make_registry_codec(&self.dimensions(), &self.biomes());
+            codec.insert("custom", ncd.username.as_str());
+            Some(codec)
+        }
+    }
+
+    #[test]
+    fn dimension_names_round_trip() {
+        let shared = setup_server(MultiDimensionConfig).unwrap();
+
+        let Some(Value::Compound(dimension_types)) =
+            shared.registry_codec().get("minecraft:dimension_type")
+        else {
+            panic!("missing dimension type registry")
+        };
+
+        let Some(Value::List(List::Compound(entries))) = dimension_types.get("value") else {
+            panic!("missing dimension type registry entries")
+        };
+
+        assert_eq!(entries.len(), 3);
+
+        for (n, (id, _)) in shared.dimensions().enumerate() {
+            let name = id.dimension_name();
+            assert_eq!(shared.dimension_id_of(name.as_str_ident()), Some(id));
+            assert_eq!(shared.dimension_by_name(name.as_str_ident()).unwrap().0, id);
+
+            // The registry codec must list the dimension at the same index.
+            assert_eq!(entries[n].get("id"), Some(&Value::Int(n as i32)));
+            assert_eq!(
+                entries[n].get("name"),
+                Some(&id.dimension_type_name().into())
+            );
+        }
+
+        let unknown = Ident::new("minecraft:overworld").unwrap();
+        assert_eq!(shared.dimension_id_of(unknown), None);
+    }
+
+    fn new_server<C: Config<ServerState = ()>>(shared: &SharedServer<C>) -> Server<C> {
+        Server {
+            state: (),
+            shared: shared.clone(),
+            clients: Clients::new(),
+            entities: Entities::new(),
+            worlds: Worlds::new(shared.clone()),
+            player_lists: PlayerLists::new(shared.compression_threshold()),
+            inventories: Inventories::new(),
+            current_tick: 0,
+            last_tick_duration: Default::default(),
+        }
+    }
+
+    /// Queues a client as if it had just finished logging in.
+    fn queue_new_client(shared: &SharedServer<impl Config>) -> MockConnection {
+        let (conn, send, recv) = MockConnection::new(1 << 20);
+
+        let msg = NewClientMessage {
+            ncd: NewClientData {
+                username: Username::new("test".to_owned()).unwrap(),
+                uuid: Uuid::from_bytes([1; 16]),
+                ip: "127.0.0.1".parse().unwrap(),
+                textures: None,
+            },
+            send,
+            recv,
+            permit: Arc::new(Semaphore::new(1)).try_acquire_owned().unwrap(),
+        };
+
+        shared.0.new_clients_send.try_send(msg).unwrap();
+
+        conn
+    }
+
+    #[test]
+    fn client_join_and_leave_fire_once() {
+        let shared = setup_server(JoinLeaveConfig::default()).unwrap();
+        let _guard = shared.tokio_handle().enter();
+
+        let mut server = new_server(&shared);
+
+        let conn = queue_new_client(&shared);
+        let joined = insert_new_clients(&mut server);
+        let id = joined[0];
+
+        let counts = || {
+            (
+                shared.config().joins.load(Ordering::SeqCst),
+                shared.config().leaves.load(Ordering::SeqCst),
+            )
+        };
+
+        notify_joins_and_leaves(&mut server, &joined);
+        assert_eq!(counts(), (1, 0));
+
+        notify_joins_and_leaves(&mut server, &[]);
+        assert_eq!(counts(), (1, 0));
+
+        // Closing the remote end disconnects the client.
+        drop(conn);
+        server.clients.get_mut(id).unwrap().prepare_c2s_packets();
+
+        notify_joins_and_leaves(&mut server, &[]);
+        assert_eq!(counts(), (1, 1));
+
+        notify_joins_and_leaves(&mut server, &[]);
+        assert_eq!(counts(), (1, 1));
+
+        // Removing a client that was already notified does not notify again.
+        server.clients.remove(id);
+        notify_joins_and_leaves(&mut server, &[]);
+        assert_eq!(counts(), (1, 1));
+    }
+
+    #[test]
+    fn removed_client_leave_fires_once() {
+        let shared = setup_server(JoinLeaveConfig::default()).unwrap();
+        let _guard = shared.tokio_handle().enter();
+
+        let mut server = new_server(&shared);
+
+        let counts = || {
+            (
+                shared.config().joins.load(Ordering::SeqCst),
+                shared.config().leaves.load(Ordering::SeqCst),
+            )
+        };
+
+        let _conn = queue_new_client(&shared);
+        let joined = insert_new_clients(&mut server);
+        notify_joins_and_leaves(&mut server, &joined);
+        assert_eq!(counts(), (1, 0));
+
+        // The client is still connected when it is removed.
+        server.clients.remove(joined[0]);
+
+        notify_joins_and_leaves(&mut server, &[]);
+        assert_eq!(counts(), (1, 1));
+
+        let _conn = queue_new_client(&shared);
+        let joined = insert_new_clients(&mut server);
+        notify_joins_and_leaves(&mut server, &joined);
+        assert_eq!(counts(), (2, 1));
+
+        server.clients.retain(|_, _| false);
+
+        notify_joins_and_leaves(&mut server, &[]);
+        assert_eq!(counts(), (2, 2));
+
+        notify_joins_and_leaves(&mut server, &[]);
+        assert_eq!(counts(), (2, 2));
+    }
+
+    #[test]
+    fn auto_managed_player_list_entries_follow_clients() {
+        let shared = setup_server(AutoPlayerListConfig).unwrap();
+        let _guard = shared.tokio_handle().enter();
+
+        let mut server = new_server(&shared);
+        let (list, _) = server.player_lists.insert(());
+        let uuid = Uuid::from_bytes([1; 16]);
+
+        let conn = queue_new_client(&shared);
+        let joined = insert_new_clients(&mut server);
+
+        let client = server.clients.get_mut(joined[0]).unwrap();
+        client.set_player_list(list.clone());
+        client.set_game_mode(GameMode::Creative);
+
+        server
+            .clients
+            .update_player_list_entries(&mut server.player_lists);
+
+        let entry = server.player_lists.get(&list).entry(uuid).unwrap();
+        assert_eq!(entry.username(), "test");
+        assert_eq!(entry.game_mode(), GameMode::Creative);
+
+        // Disconnecting removes the entry.
+        drop(conn);
+        server
+            .clients
+            .get_mut(joined[0])
+            .unwrap()
+            .prepare_c2s_packets();
+
+        server
+            .clients
+            .update_player_list_entries(&mut server.player_lists);
+
+        assert!(server.player_lists.get(&list).entry(uuid).is_none());
+
+        // So does removing a connected client.
+        let _conn = queue_new_client(&shared);
+        let joined = insert_new_clients(&mut server);
+
+        server
+            .clients
+            .get_mut(joined[0])
+            .unwrap()
+            .set_player_list(list.clone());
+
+        server
+            .clients
+            .update_player_list_entries(&mut server.player_lists);
+        assert!(server.player_lists.get(&list).entry(uuid).is_some());
+
+        server.clients.remove(joined[0]);
+
+        server
+            .clients
+            .update_player_list_entries(&mut server.player_lists);
+        assert!(server.player_lists.get(&list).entry(uuid).is_none());
+    }
+
+    #[test]
+    fn created_tick_is_join_tick() {
+        let shared = setup_server(MockConfig::default()).unwrap();
+        let _guard = shared.tokio_handle().enter();
+
+        let mut server = new_server(&shared);
+        server.current_tick = 42;
+
+        let _conn = queue_new_client(&shared);
+        let joined = insert_new_clients(&mut server);
+
+        assert_eq!(joined.len(), 1);
+
+        let client = server.clients.get(joined[0]).unwrap();
+        assert_eq!(client.created_tick(), 42);
+        assert!(client.created_this_tick());
+    }
+
+    #[test]
+    fn counts_reflect_creates_and_deletes() {
+        let shared = setup_server(MockConfig::default()).unwrap();
+        let _guard = shared.tokio_handle().enter();
+
+        let mut server = new_server(&shared);
+
+        let counts = || {
+            (
+                shared.client_count(),
+                shared.entity_count(),
+                shared.world_count(),
+                shared.loaded_chunk_count(),
+            )
+        };
+
+        update_counts(&mut server);
+        assert_eq!(counts(), (0, 0, 0, 0));
+
+        let _conn = queue_new_client(&shared);
+        let joined = insert_new_clients(&mut server);
+
+        let (entity_id, _) = server.entities.insert(EntityKind::Zombie, ());
+
+        let (world_id, world) = server.worlds.insert(DimensionId::default(), ());
+        for pos in [[0, 0], [0, 1], [1, 0]] {
+            world.chunks.insert(pos, UnloadedChunk::default(), ());
+        }
+
+        // Overwriting a chunk does not change the count.
+        world.chunks.insert([0, 0], UnloadedChunk::default(), ());
+
+        // The loaded chunk count is updated without waiting for the tick to
+        // end.
+        assert_eq!(shared.loaded_chunk_count(), 3);
+
+        let (_, other_world) = server.worlds.insert(DimensionId::default(), ());
+        other_world
+            .chunks
+            .insert([0, 0], UnloadedChunk::default(), ());
+
+        update_counts(&mut server);
+        assert_eq!(counts(), (1, 1, 2, 4));
+
+        server.clients.remove(joined[0]);
+        server.entities[entity_id].set_deleted(true);
+        server.worlds[world_id].chunks[[0, 1]].set_deleted(true);
+
+        server.entities.update();
+        server.worlds.update();
+
+        update_counts(&mut server);
+        assert_eq!(counts(), (0, 0, 2, 3));
+
+        server.worlds.remove(world_id);
+
+        update_counts(&mut server);
+        assert_eq!(counts(), (0, 0, 1, 1));
+    }
+
+    #[test]
+    fn slow_ticks_lower_tps() {
+        let shared = setup_server(MockConfig::default()).unwrap();
+        let window = shared.config().tps_window();
+
+        let mut now = Instant::now();
+        assert_eq!(shared.tps(), shared.tick_rate() as f64);
+
+        for _ in 0..=window {
+            now += Duration::from_millis(50);
+            shared.record_tick_start(now);
+        }
+
+        assert!((shared.tps() - 20.0).abs() < 0.01);
+
+        // Ticks taking twice as long halve the TPS once the window is full of
+        // them.
+        for i in 0..window {
+            now += Duration::from_millis(100);
+            shared.record_tick_start(now);
+
+            if i == window / 2 {
+                assert!(shared.tps() < 20.0 && shared.tps() > 10.0);
+            }
+        }
+
+        assert!((shared.tps() - 10.0).abs() < 0.01);
+    }
+
+    #[test]
+    fn login_uses_per_client_registry_codec() {
+        let shared = setup_server(CustomCodecConfig).unwrap();
+        let _guard = shared.tokio_handle().enter();
+
+        let mut server = new_server(&shared);
+
+        let mut conn = queue_new_client(&shared);
+        let joined = insert_new_clients(&mut server);
+
+        let (world_id, _) = server.worlds.insert(DimensionId::default(), ());
+
+        let client = server.clients.get_mut(joined[0]).unwrap();
+        client.respawn(world_id);
+        client.update(
+            0,
+            &shared,
+            &server.entities,
+            &server.worlds,
+            &server.player_lists,
+            &server.inventories,
+        );
+
+        let mut logged_in = false;
+
+        conn.for_each_packet(|pkt| {
+            if let S2cPlayPacket::LoginPlay(p) = pkt {
+                assert_eq!(p.registry_codec.get("custom"), Some(&"test".into()));
+                assert!(shared.registry_codec().get("custom").is_none());
+                logged_in = true;
+            }
+        });
+
+        assert!(logged_in);
+    }
+}
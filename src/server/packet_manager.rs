@@ -9,9 +9,13 @@ use tokio::sync::OwnedSemaphorePermit;
 use tokio::task::JoinHandle;
 use tokio::time::timeout;
 use tracing::debug;
+#[cfg(test)]
+use valence_protocol::packets::S2cPlayPacket;
 use valence_protocol::{Decode, Encode, Packet, PacketDecoder, PacketEncoder};
 
 use crate::packet::WritePacket;
+#[cfg(feature = "packet_capture")]
+use crate::packet_capture::{PacketCapture, PacketDirection};
 use crate::server::byte_channel::{byte_channel, ByteReceiver, ByteSender, TryRecvError};
 
 pub struct InitialPacketManager<R, W> {
@@ -173,11 +177,15 @@ where
                 send: outgoing_sender,
                 writer_task: Some(writer_task),
                 handle,
+                #[cfg(feature = "packet_capture")]
+                capture: None,
             },
             PlayPacketReceiver {
                 dec: self.dec,
                 recv: incoming_receiver,
                 reader_task,
+                #[cfg(feature = "packet_capture")]
+                capture: None,
             },
             self.permit,
         )
@@ -191,6 +199,8 @@ pub struct PlayPacketSender {
     send: ByteSender,
     writer_task: Option<JoinHandle<()>>,
     handle: Handle,
+    #[cfg(feature = "packet_capture")]
+    capture: Option<PacketCapture>,
 }
 
 impl PlayPacketSender {
@@ -198,10 +208,20 @@ impl PlayPacketSender {
     where
         P: Encode + Packet + ?Sized,
     {
+        #[cfg(feature = "packet_capture")]
+        if let Some(capture) = &self.capture {
+            capture.record(PacketDirection::Clientbound, pkt);
+        }
+
         self.enc.append_packet(pkt)
     }
 
     pub fn append_bytes(&mut self, bytes: &[u8]) {
+        #[cfg(feature = "packet_capture")]
+        if let Some(capture) = &self.capture {
+            capture.record_frames(bytes, self.enc.compression_threshold().is_some());
+        }
+
         self.enc.append_bytes(bytes)
     }
 
@@ -209,9 +229,20 @@ impl PlayPacketSender {
     where
         P: Encode + Packet + ?Sized,
     {
+        #[cfg(feature = "packet_capture")]
+        if let Some(capture) = &self.capture {
+            capture.record(PacketDirection::Clientbound, pkt);
+        }
+
         self.enc.prepend_packet(pkt)
     }
 
+    /// Sets the capture that packets are recorded to. `None` stops recording.
+    #[cfg(feature = "packet_capture")]
+    pub fn set_capture(&mut self, capture: Option<PacketCapture>) {
+        self.capture = capture;
+    }
+
     pub fn flush(&mut self) -> Result<()> {
         let bytes = self.enc.take();
         self.send.try_send(bytes)?;
@@ -254,9 +285,12 @@ pub struct PlayPacketReceiver {
     dec: PacketDecoder,
     recv: ByteReceiver,
     reader_task: JoinHandle<()>,
+    #[cfg(feature = "packet_capture")]
+    capture: Option<PacketCapture>,
 }
 
 impl PlayPacketReceiver {
+    #[cfg(not(feature = "packet_capture"))]
     pub fn try_next_packet<'a, P>(&'a mut self) -> Result<Option<P>>
     where
         P: Decode<'a> + Packet,
@@ -264,6 +298,33 @@ impl PlayPacketReceiver {
         self.dec.try_next_packet()
     }
 
+    // Received packets are encoded again to be recorded, which requires the
+    // extra `Encode` bound.
+    #[cfg(feature = "packet_capture")]
+    pub fn try_next_packet<'a, P>(&'a mut self) -> Result<Option<P>>
+    where
+        P: Decode<'a> + Encode + Packet,
+    {
+        let pkt = self.dec.try_next_packet()?;
+
+        if let (Some(capture), Some(pkt)) = (&self.capture, &pkt) {
+            capture.record(PacketDirection::Serverbound, pkt);
+        }
+
+        Ok(pkt)
+    }
+
+    /// Sets the capture that packets are recorded to. `None` stops recording.
+    #[cfg(feature = "packet_capture")]
+    pub fn set_capture(&mut self, capture: Option<PacketCapture>) {
+        self.capture = capture;
+    }
+
+    #[cfg(feature = "packet_capture")]
+    pub fn take_capture(&mut self) -> Option<PacketCapture> {
+        self.capture.take()
+    }
+
     /// Returns true if the client is connected. Returns false otherwise.
     pub fn try_recv(&mut self) -> bool {
         match self.recv.try_recv() {
@@ -282,3 +343,73 @@ impl Drop for PlayPacketReceiver {
         self.reader_task.abort();
     }
 }
+
+/// The remote end of a [`PlayPacketSender`] and [`PlayPacketReceiver`] pair
+/// which is not backed by a real connection. Used for testing.
+#[cfg(test)]
+pub(crate) struct MockConnection {
+    /// Receives the bytes flushed by the [`PlayPacketSender`].
+    outgoing: ByteReceiver,
+    /// Sends bytes to the [`PlayPacketReceiver`].
+    incoming: ByteSender,
+    dec: PacketDecoder,
+}
+
+#[cfg(test)]
+impl MockConnection {
+    /// Creates a new mock connection along with the packet sender and receiver
+    /// connected to it. Must be called from within a tokio runtime.
+    pub fn new(capacity: usize) -> (Self, PlayPacketSender, PlayPacketReceiver) {
+        let (incoming_sender, incoming_receiver) = byte_channel(capacity);
+        let (outgoing_sender, outgoing_receiver) = byte_channel(capacity);
+        let handle = Handle::current();
+
+        let conn = Self {
+            outgoing: outgoing_receiver,
+            incoming: incoming_sender,
+            dec: PacketDecoder::new(),
+        };
+
+        let send = PlayPacketSender {
+            enc: PacketEncoder::new(),
+            send: outgoing_sender,
+            writer_task: None,
+            handle: handle.clone(),
+            #[cfg(feature = "packet_capture")]
+            capture: None,
+        };
+
+        let recv = PlayPacketReceiver {
+            dec: PacketDecoder::new(),
+            recv: incoming_receiver,
+            reader_task: handle.spawn(async {}),
+            #[cfg(feature = "packet_capture")]
+            capture: None,
+        };
+
+        (conn, send, recv)
+    }
+
+    /// Encodes a packet and sends it to the [`PlayPacketReceiver`]. The packet
+    /// can be read once the receiver has received the pending bytes.
+    pub fn send_packet<P>(&mut self, pkt: &P)
+    where
+        P: Encode + Packet + ?Sized,
+    {
+        let mut enc = PacketEncoder::new();
+        enc.append_packet(pkt).unwrap();
+        self.incoming.try_send(enc.take()).unwrap();
+    }
+
+    /// Decodes every packet flushed by the [`PlayPacketSender`] so far and
+    /// passes it to `f` in the order it was sent.
+    pub fn for_each_packet(&mut self, mut f: impl FnMut(S2cPlayPacket)) {
+        if let Ok(bytes) = self.outgoing.try_recv() {
+            self.dec.queue_bytes(bytes);
+        }
+
+        while let Some(pkt) = self.dec.try_next_packet::<S2cPlayPacket>().unwrap() {
+            f(pkt);
+        }
+    }
+}
@@ -52,6 +52,36 @@ pub fn from_yaw_and_pitch(yaw: f64, pitch: f64) -> Vec3<f64> {
     Vec3::new(yaw.cos() * xz_len, pitch.sin(), yaw.sin() * xz_len)
 }
 
+/// Returns the pitch multiplier of a note block sound playing the given note.
+///
+/// Notes range from `0` (F♯3) to `24` (F♯5). Note `12` has a pitch of `1.0`
+/// and each octave doubles the pitch. Notes above `24` are clamped.
+pub fn note_pitch(note: u8) -> f32 {
+    2.0_f32.powf((note.min(24) as f32 - 12.0) / 12.0)
+}
+
+/// Returns the total number of experience points a player has at the given
+/// level with the XP bar filled to `bar`, using the vanilla level curve.
+///
+/// `level` is clamped to be nonnegative and `bar` is clamped between `0.0`
+/// and `1.0`. Partial points on the bar are rounded down.
+pub fn xp_at_level(level: i32, bar: f32) -> i32 {
+    let level = level.max(0) as i128;
+
+    let (total, next) = match level {
+        0..=15 => (level * level + 6 * level, 2 * level + 7),
+        16..=30 => ((5 * level * level - 81 * level + 720) / 2, 5 * level - 38),
+        _ => (
+            (9 * level * level - 325 * level + 4440) / 2,
+            9 * level - 158,
+        ),
+    };
+
+    let progress = (bar.clamp(0.0, 1.0) as f64 * next as f64) as i128;
+
+    (total + progress).min(i32::MAX as i128) as i32
+}
+
 /// Calculates the minimum number of bits needed to represent the integer `n`.
 /// Also known as `floor(log2(n)) + 1`.
 ///
@@ -78,4 +108,30 @@ mod tests {
             assert_relative_eq!(d, d_new, epsilon = f64::EPSILON * 100.0);
         }
     }
+
+    #[test]
+    fn note_pitches() {
+        assert_eq!(note_pitch(12), 1.0);
+        assert_relative_eq!(note_pitch(0), 0.5);
+        assert_relative_eq!(note_pitch(24), 2.0);
+        assert_relative_eq!(note_pitch(255), 2.0);
+    }
+
+    #[test]
+    fn xp_at_vanilla_levels() {
+        assert_eq!(xp_at_level(0, 0.0), 0);
+        assert_eq!(xp_at_level(1, 0.0), 7);
+        assert_eq!(xp_at_level(16, 0.0), 352);
+        assert_eq!(xp_at_level(17, 0.0), 394);
+        assert_eq!(xp_at_level(31, 0.0), 1507);
+        assert_eq!(xp_at_level(32, 0.0), 1628);
+
+        // A full bar is worth the same as the next level.
+        assert_eq!(xp_at_level(16, 1.0), xp_at_level(17, 0.0));
+        assert_eq!(xp_at_level(31, 1.0), xp_at_level(32, 0.0));
+        assert_eq!(xp_at_level(16, 0.5), 352 + 21);
+
+        assert_eq!(xp_at_level(-5, 0.0), 0);
+        assert_eq!(xp_at_level(i32::MAX, 1.0), i32::MAX);
+    }
 }
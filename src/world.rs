@@ -4,12 +4,19 @@ use std::iter::FusedIterator;
 use std::ops::{Deref, DerefMut, Index, IndexMut};
 
 use rayon::iter::ParallelIterator;
+use sha2::{Digest, Sha256};
+use valence_protocol::packets::s2c::play::CustomSoundEffect;
+use valence_protocol::types::SoundCategory;
+use valence_protocol::{BlockPos, BlockState, Ident};
+use vek::Vec3;
 
-use crate::chunk::Chunks;
+use crate::chunk::{Chunk, ChunkPos, Chunks};
 use crate::config::Config;
 use crate::dimension::DimensionId;
+use crate::packet::WritePacket;
 use crate::server::SharedServer;
 use crate::slab_versioned::{Key, VersionedSlab};
+use crate::Ticks;
 
 /// A container for all [`World`]s on a [`Server`](crate::server::Server).
 pub struct Worlds<C: Config> {
@@ -58,9 +65,15 @@ impl<C: Config> Worlds<C> {
                 dim.min_y,
                 self.shared.biomes().len(),
                 self.shared.compression_threshold(),
+                self.shared.loaded_chunk_counter(),
             ),
             dimension,
+            terrain_seed: 0,
+            hashed_seed: hash_seed(0),
             deleted: false,
+            fallback: None,
+            scheduled_block_updates: vec![],
+            sounds: vec![],
         });
 
         (WorldId(id), world)
@@ -75,6 +88,30 @@ impl<C: Config> Worlds<C> {
         self.slab.remove(world.0).map(|w| w.state)
     }
 
+    /// Marks a world as deleted like [`World::set_deleted`], but clients that
+    /// are still in the world at the end of the tick are respawned in
+    /// `fallback_world` at `fallback_pos` instead of being disconnected.
+    ///
+    /// Entities located in the deleted world, including the player entities
+    /// of the moved clients, are not moved.
+    ///
+    /// Returns `false` if `world` is invalid.
+    pub fn delete_with_fallback(
+        &mut self,
+        world: WorldId,
+        fallback_world: WorldId,
+        fallback_pos: impl Into<Vec3<f64>>,
+    ) -> bool {
+        match self.get_mut(world) {
+            Some(world) => {
+                world.deleted = true;
+                world.fallback = Some((fallback_world, fallback_pos.into()));
+                true
+            }
+            None => false,
+        }
+    }
+
     /// Removes all worlds from the server for which `f` returns `false`.
     ///
     /// All worlds are visited in an unspecified order.
@@ -137,6 +174,7 @@ impl<C: Config> Worlds<C> {
 
         self.par_iter_mut().for_each(|(_, world)| {
             world.chunks.update();
+            world.sounds.clear();
         });
     }
 }
@@ -161,7 +199,27 @@ pub struct World<C: Config> {
     pub state: C::WorldState,
     pub chunks: Chunks<C>,
     dimension: DimensionId,
+    terrain_seed: i64,
+    /// The hash of `terrain_seed` sent to clients.
+    hashed_seed: i64,
     deleted: bool,
+    /// Where clients are sent if this world is deleted.
+    fallback: Option<(WorldId, Vec3<f64>)>,
+    /// Block changes waiting to be applied along with the number of ticks
+    /// remaining until they are.
+    scheduled_block_updates: Vec<(BlockPos, BlockState, Ticks)>,
+    /// Sounds played this tick which are sent to nearby clients.
+    sounds: Vec<WorldSound>,
+}
+
+/// A sound played with [`World::play_sound`].
+struct WorldSound {
+    name: Ident<String>,
+    category: SoundCategory,
+    position: Vec3<f64>,
+    volume: f32,
+    pitch: f32,
+    seed: u64,
 }
 
 impl<C: Config> Deref for World<C> {
@@ -184,6 +242,28 @@ impl<C: Config> World<C> {
         self.dimension
     }
 
+    /// Gets the terrain seed of this world. See [`Self::set_terrain_seed`].
+    pub fn terrain_seed(&self) -> i64 {
+        self.terrain_seed
+    }
+
+    /// Sets the seed clients in this world use for client-side effects such as
+    /// biome blending. The seed itself is never sent to clients, only a hash
+    /// of it.
+    ///
+    /// The same hashed seed is sent when clients join and when they respawn,
+    /// so the effects are consistent. Changes take effect the next time a
+    /// client joins or is respawned in this world. The default seed is `0`.
+    pub fn set_terrain_seed(&mut self, seed: i64) {
+        self.terrain_seed = seed;
+        self.hashed_seed = hash_seed(seed);
+    }
+
+    /// Gets the hash of the terrain seed which is sent to clients.
+    pub(crate) fn hashed_seed(&self) -> i64 {
+        self.hashed_seed
+    }
+
     pub fn deleted(&self) -> bool {
         self.deleted
     }
@@ -193,8 +273,176 @@ impl<C: Config> World<C> {
     ///
     /// Note that any entities located in the world are not deleted and their
     /// location will not change. Additionally, clients that are still in
-    /// the deleted world at the end of the tick are disconnected.
+    /// the deleted world at the end of the tick are disconnected. To move them
+    /// elsewhere instead, see [`Worlds::delete_with_fallback`].
     pub fn set_deleted(&mut self, deleted: bool) {
         self.deleted = deleted;
+
+        if !deleted {
+            self.fallback = None;
+        }
+    }
+
+    /// Schedules the block at `pos` to be set to `state` after `delay` ticks
+    /// have passed. This is useful for delayed effects such as closing doors
+    /// or disappearing platforms.
+    ///
+    /// Scheduled updates are applied near the end of the tick, before chunk
+    /// changes are sent to clients. A `delay` of zero or less applies the
+    /// update at the end of the current tick. Updates scheduled for the same
+    /// tick are applied in the order they were scheduled.
+    ///
+    /// If the chunk containing `pos` is not loaded when the update is due, the
+    /// update is discarded and no chunk is created.
+    pub fn schedule_block_update(
+        &mut self,
+        pos: impl Into<BlockPos>,
+        state: BlockState,
+        delay: Ticks,
+    ) {
+        self.scheduled_block_updates
+            .push((pos.into(), state, delay.max(0)));
+    }
+
+    /// Returns the number of scheduled block updates that have not been
+    /// applied yet.
+    pub fn scheduled_block_update_count(&self) -> usize {
+        self.scheduled_block_updates.len()
+    }
+
+    /// Removes all scheduled block updates without applying them.
+    pub fn clear_scheduled_block_updates(&mut self) {
+        self.scheduled_block_updates.clear();
+    }
+
+    /// Applies the scheduled block updates that are due and counts down the
+    /// rest. Called once per tick.
+    pub(crate) fn apply_scheduled_block_updates(&mut self) {
+        let chunks = &mut self.chunks;
+
+        self.scheduled_block_updates
+            .retain_mut(|(pos, state, delay)| {
+                if *delay > 0 {
+                    *delay -= 1;
+                    return true;
+                }
+
+                let min_y = chunks.min_y();
+
+                if let Some(chunk) = chunks.get_mut(ChunkPos::from(*pos)) {
+                    if let Some(y) = pos
+                        .y
+                        .checked_sub(min_y)
+                        .and_then(|y| usize::try_from(y).ok())
+                        .filter(|&y| y < chunk.height())
+                    {
+                        chunk.set_block_state(
+                            pos.x.rem_euclid(16) as usize,
+                            y,
+                            pos.z.rem_euclid(16) as usize,
+                            *state,
+                        );
+                    }
+                }
+
+                false
+            });
+    }
+
+    /// Plays a sound at a position in this world. At the end of the tick, the
+    /// sound is sent to every client in this world within hearing range.
+    ///
+    /// Like in vanilla, the range is 16 blocks for volumes up to `1.0` and
+    /// `16 * volume` blocks for louder sounds.
+    pub fn play_sound(
+        &mut self,
+        pos: impl Into<Vec3<f64>>,
+        name: Ident<&str>,
+        category: SoundCategory,
+        volume: f32,
+        pitch: f32,
+    ) {
+        self.sounds.push(WorldSound {
+            name: name.into(),
+            category,
+            position: pos.into(),
+            volume,
+            pitch,
+            seed: rand::random(),
+        });
+    }
+
+    /// Writes the packets for the sounds played this tick which can be heard
+    /// from `listener`.
+    pub(crate) fn write_sound_packets(
+        &self,
+        mut writer: impl WritePacket,
+        listener: Vec3<f64>,
+    ) -> anyhow::Result<()> {
+        for sound in &self.sounds {
+            let range = 16.0 * sound.volume.max(1.0) as f64;
+
+            if sound.position.distance_squared(listener) <= range * range {
+                writer.write_packet(&CustomSoundEffect {
+                    name: sound.name.as_str_ident(),
+                    category: sound.category,
+                    position: (sound.position.as_() * 8).into_array(),
+                    volume: sound.volume,
+                    pitch: sound.pitch,
+                    seed: sound.seed,
+                })?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Gets the world and position clients are sent to if this world was
+    /// deleted with [`Worlds::delete_with_fallback`].
+    pub(crate) fn fallback(&self) -> Option<(WorldId, Vec3<f64>)> {
+        if self.deleted {
+            self.fallback
+        } else {
+            None
+        }
+    }
+}
+
+/// Hashes a seed the same way as the vanilla server: The first 8 bytes of the
+/// SHA-256 hash of the seed, both in little endian.
+fn hash_seed(seed: i64) -> i64 {
+    let hash = Sha256::digest(seed.to_le_bytes());
+    i64::from_le_bytes(hash[..8].try_into().unwrap())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chunk::UnloadedChunk;
+    use crate::config::MockConfig;
+    use crate::server::setup_server;
+
+    #[test]
+    fn scheduled_block_update_applies_on_due_tick() {
+        let shared = setup_server(MockConfig::default()).unwrap();
+        let mut worlds = Worlds::new(shared);
+
+        let (_, world) = worlds.insert(DimensionId::default(), ());
+        world.chunks.insert([0, 0], UnloadedChunk::default(), ());
+
+        world.schedule_block_update([1, 2, 3], BlockState::STONE, 2);
+        // The chunk at this position is not loaded.
+        world.schedule_block_update([100, 2, 100], BlockState::STONE, 1);
+
+        for _ in 0..2 {
+            world.apply_scheduled_block_updates();
+            assert_eq!(world.chunks.block_state([1, 2, 3]), Some(BlockState::AIR));
+        }
+
+        world.apply_scheduled_block_updates();
+        assert_eq!(world.chunks.block_state([1, 2, 3]), Some(BlockState::STONE));
+
+        assert_eq!(world.scheduled_block_update_count(), 0);
+        assert_eq!(world.chunks.len(), 1);
     }
 }
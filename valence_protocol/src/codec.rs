@@ -184,6 +184,11 @@ impl PacketEncoder {
         self.compression_threshold = threshold;
     }
 
+    #[cfg(feature = "compression")]
+    pub fn compression_threshold(&self) -> Option<u32> {
+        self.compression_threshold
+    }
+
     /// Encrypts all future packets **and any packets that have
     /// not been [taken] yet.**
     ///
@@ -9,9 +9,9 @@ use crate::item::ItemStack;
 use crate::raw_bytes::RawBytes;
 use crate::text::Text;
 use crate::types::{
-    AttributeProperty, BossBarAction, ChunkDataBlockEntity, DeathLocation, Difficulty, GameMode,
-    GameStateChangeReason, PlayerInfoAddPlayer, SignedProperty, SoundCategory,
-    SyncPlayerPosLookFlags,
+    AttributeProperty, BossBarAction, ChunkDataBlockEntity, DeathLocation, Difficulty,
+    EquipmentList, GameMode, GameStateChangeReason, PlayerAbilitiesFlags, PlayerInfoAddPlayer,
+    SignedProperty, SoundCategory, StopSoundFilter, SyncPlayerPosLookFlags,
 };
 use crate::username::Username;
 use crate::var_int::VarInt;
@@ -186,6 +186,12 @@ pub mod play {
         pub reset: bool,
     }
 
+    #[derive(Copy, Clone, Debug, Encode, Decode, Packet)]
+    #[packet_id = 0x10]
+    pub struct CloseContainerS2c {
+        pub window_id: u8,
+    }
+
     #[derive(Clone, Debug, Encode, Decode, Packet)]
     #[packet_id = 0x11]
     pub struct SetContainerContent {
@@ -428,6 +434,14 @@ pub mod play {
         pub window_title: Text,
     }
 
+    #[derive(Copy, Clone, Debug, Encode, Decode, Packet)]
+    #[packet_id = 0x31]
+    pub struct PlayerAbilitiesS2c {
+        pub flags: PlayerAbilitiesFlags,
+        pub flying_speed: f32,
+        pub fov_modifier: f32,
+    }
+
     #[derive(Clone, Debug, Encode, Decode, Packet)]
     #[packet_id = 0x33]
     pub struct PlayerChatMessage<'a> {
@@ -580,6 +594,13 @@ pub mod play {
         pub velocity: [i16; 3],
     }
 
+    #[derive(Clone, PartialEq, Debug, Encode, Decode, Packet)]
+    #[packet_id = 0x53]
+    pub struct SetEquipment {
+        pub entity_id: VarInt,
+        pub equipment: EquipmentList,
+    }
+
     #[derive(Copy, Clone, Debug, Encode, Decode, Packet)]
     #[packet_id = 0x54]
     pub struct SetExperience {
@@ -596,6 +617,20 @@ pub mod play {
         pub food_saturation: f32,
     }
 
+    #[derive(Clone, PartialEq, Debug, Encode, Decode, Packet)]
+    #[packet_id = 0x57]
+    pub struct SetPassengers {
+        /// The vehicle.
+        pub entity_id: VarInt,
+        /// The entities riding the vehicle. An empty list dismounts every
+        /// passenger.
+        pub passengers: Vec<VarInt>,
+    }
+
+    #[derive(Copy, Clone, Debug, Encode, Decode, Packet)]
+    #[packet_id = 0x5a]
+    pub struct SetSimulationDistance(pub VarInt);
+
     #[derive(Clone, Debug, Encode, Decode, Packet)]
     #[packet_id = 0x5b]
     pub struct SetSubtitleText(pub Text);
@@ -647,6 +682,12 @@ pub mod play {
         pub seed: i64,
     }
 
+    #[derive(Copy, Clone, Debug, Encode, Decode, Packet)]
+    #[packet_id = 0x61]
+    pub struct StopSound<'a> {
+        pub filter: StopSoundFilter<'a>,
+    }
+
     #[derive(Clone, Debug, Encode, Decode, Packet)]
     #[packet_id = 0x62]
     pub struct SystemChatMessage {
@@ -693,6 +734,7 @@ pub mod play {
             BossBar,
             SetDifficulty,
             ClearTitles,
+            CloseContainerS2c,
             SetContainerContent,
             SetContainerProperty,
             SetContainerSlot,
@@ -712,6 +754,7 @@ pub mod play {
             UpdateEntityPositionAndRotation,
             UpdateEntityRotation,
             OpenScreen,
+            PlayerAbilitiesS2c,
             PlayerChatMessage<'a>,
             CombatDeath,
             PlayerInfo<'a>,
@@ -728,14 +771,18 @@ pub mod play {
             SetDefaultSpawnPosition,
             SetEntityMetadata<'a>,
             SetEntityVelocity,
+            SetEquipment,
             SetExperience,
             SetHealth,
+            SetPassengers,
+            SetSimulationDistance,
             SetSubtitleText,
             UpdateTime,
             SetTitleText,
             SetTitleAnimationTimes,
             EntitySoundEffect,
             SoundEffect,
+            StopSound<'a>,
             SystemChatMessage,
             SetTabListHeaderAndFooter,
             TeleportEntity,
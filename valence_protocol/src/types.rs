@@ -1,11 +1,14 @@
 //! Miscellaneous type definitions used in packets.
 
+use std::io::Write;
+
+use anyhow::{bail, ensure};
 use bitfield_struct::bitfield;
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 use valence_nbt::Compound;
 
-use crate::{BlockPos, Decode, Encode, Ident, Text, VarInt};
+use crate::{BlockPos, Decode, Encode, Ident, ItemStack, Text, VarInt};
 
 #[derive(Copy, Clone, Debug, PartialEq, Eq, Encode, Decode)]
 pub enum HandshakeNextState {
@@ -77,6 +80,137 @@ pub enum Hand {
     Off,
 }
 
+/// A slot an entity can hold or wear an item in.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum EquipmentSlot {
+    MainHand,
+    OffHand,
+    Boots,
+    Leggings,
+    Chestplate,
+    Helmet,
+}
+
+impl EquipmentSlot {
+    /// All equipment slots in the order of their protocol IDs.
+    pub const ALL: [Self; 6] = [
+        Self::MainHand,
+        Self::OffHand,
+        Self::Boots,
+        Self::Leggings,
+        Self::Chestplate,
+        Self::Helmet,
+    ];
+
+    /// Returns the protocol ID of this slot.
+    pub const fn to_raw(self) -> u8 {
+        self as u8
+    }
+
+    /// Returns the slot with the given protocol ID, or `None` if the ID is
+    /// invalid.
+    pub const fn from_raw(raw: u8) -> Option<Self> {
+        if (raw as usize) < Self::ALL.len() {
+            Some(Self::ALL[raw as usize])
+        } else {
+            None
+        }
+    }
+}
+
+/// The list of equipment changes in a
+/// [`SetEquipment`](crate::packets::s2c::play::SetEquipment) packet.
+///
+/// Every slot is written as a single byte followed by the item in that slot.
+/// The top bit of the slot byte is set if another entry follows. The list must
+/// not be empty.
+#[derive(Clone, PartialEq, Debug)]
+pub struct EquipmentList(pub Vec<(EquipmentSlot, Option<ItemStack>)>);
+
+impl Encode for EquipmentList {
+    fn encode(&self, mut w: impl Write) -> anyhow::Result<()> {
+        ensure!(!self.0.is_empty(), "equipment list must not be empty");
+
+        let last = self.0.len() - 1;
+        for (i, (slot, item)) in self.0.iter().enumerate() {
+            let more = if i == last { 0 } else { 0x80 };
+            (slot.to_raw() | more).encode(&mut w)?;
+            item.encode(&mut w)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl Decode<'_> for EquipmentList {
+    fn decode(r: &mut &[u8]) -> anyhow::Result<Self> {
+        let mut list = vec![];
+
+        loop {
+            let byte = u8::decode(r)?;
+            let slot = match EquipmentSlot::from_raw(byte & 0x7f) {
+                Some(slot) => slot,
+                None => bail!("invalid equipment slot of {}", byte & 0x7f),
+            };
+            list.push((slot, Option::<ItemStack>::decode(r)?));
+
+            if byte & 0x80 == 0 {
+                return Ok(Self(list));
+            }
+        }
+    }
+}
+
+/// The sounds stopped by a
+/// [`StopSound`](crate::packets::s2c::play::StopSound) packet.
+///
+/// A flags byte is written first, with bit `0x1` set if `source` is present
+/// and bit `0x2` set if `sound` is present. The present fields follow in that
+/// order. Leaving both fields empty stops every sound.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct StopSoundFilter<'a> {
+    pub source: Option<SoundCategory>,
+    pub sound: Option<Ident<&'a str>>,
+}
+
+impl Encode for StopSoundFilter<'_> {
+    fn encode(&self, mut w: impl Write) -> anyhow::Result<()> {
+        let flags = self.source.is_some() as u8 | (self.sound.is_some() as u8) << 1;
+        flags.encode(&mut w)?;
+
+        if let Some(source) = &self.source {
+            source.encode(&mut w)?;
+        }
+
+        if let Some(sound) = &self.sound {
+            sound.encode(&mut w)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl<'a> Decode<'a> for StopSoundFilter<'a> {
+    fn decode(r: &mut &'a [u8]) -> anyhow::Result<Self> {
+        let flags = u8::decode(r)?;
+        ensure!(flags & !0x3 == 0, "invalid stop sound flags of {flags}");
+
+        let source = if flags & 0x1 != 0 {
+            Some(SoundCategory::decode(r)?)
+        } else {
+            None
+        };
+
+        let sound = if flags & 0x2 != 0 {
+            Some(Ident::decode(r)?)
+        } else {
+            None
+        };
+
+        Ok(Self { source, sound })
+    }
+}
+
 #[derive(Copy, Clone, PartialEq, Debug, Encode, Decode)]
 pub enum EntityInteraction {
     Interact(Hand),
@@ -352,6 +486,17 @@ pub struct StructureBlockFlags {
     _pad: u8,
 }
 
+#[bitfield(u8)]
+#[derive(PartialEq, Eq, Encode, Decode)]
+pub struct PlayerAbilitiesFlags {
+    pub invulnerable: bool,
+    pub flying: bool,
+    pub allow_flying: bool,
+    pub instant_break: bool,
+    #[bits(4)]
+    _pad: u8,
+}
+
 #[bitfield(u8)]
 #[derive(PartialEq, Eq, Encode, Decode)]
 pub struct SyncPlayerPosLookFlags {